@@ -19,7 +19,12 @@ fn main() {
     let mut floor = Quad::new(vec3(250.0, 250.0, 250.0), Vec4::ONE).mesh();
     floor.rotation = Quat::from_euler(EulerRot::XYZ, -3.1415 * 0.5, 0.0, 0.0);
     floor.position = vec3(-125.0, 0.0, 125.0);
-    floor.color = vec3(0.3, 0.3, 0.6);
+    floor.material = renderer.add_material({
+        let mut material = Material::default();
+        material.set_color(vec3(0.3, 0.3, 0.6));
+
+        material
+    });
     // renderer.add_mesh(floor).unwrap();
     let mut culler = Culler::new(&renderer);
 
@@ -90,7 +95,7 @@ fn count_meshes_sent_to_the_gpu(renderer: &Renderer) -> i32 {
     let mut count = 0;
 
     for mesh in renderer.meshes.values() {
-        if mesh.hidden == false {
+        if !mesh.hidden && !mesh.culled {
             count += 1;
         }
     }
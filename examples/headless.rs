@@ -0,0 +1,38 @@
+/*
+Renders a lit quad and cuboid without showing a window and saves the result,
+the way a golden image test would. on a linux machine with no gpu:
+LIBGL_ALWAYS_SOFTWARE=1 xvfb-run cargo run --example headless
+*/
+
+use chaos_framework::*;
+
+fn main() {
+    let el = EventLoop::headless(256, 256);
+    let mut renderer = Renderer::new();
+
+    unsafe {
+        Enable(DEPTH_TEST);
+    }
+
+    renderer.add_light(Light::point(vec3(2.0, 3.0, 4.0), Vec3::ONE)).unwrap();
+
+    let mut quad = Quad::new(vec3(2.0, 2.0, 1.0), vec4(1.0, 0.5, 0.2, 1.0)).mesh();
+    quad.position = vec3(-2.5, -1.0, -2.0);
+    renderer.add_mesh(quad).unwrap();
+
+    let mut cuboid = Cuboid::new(Vec3::ONE, vec4(0.2, 0.6, 1.0, 1.0)).mesh();
+    cuboid.position = vec3(1.0, 0.0, -2.0);
+    cuboid.rotation = Quat::from_euler(EulerRot::XYZ, 0.5, 0.7, 0.0);
+    renderer.add_mesh(cuboid).unwrap();
+
+    renderer.update();
+    renderer.camera.update(vec3(0.0, 0.0, 3.0), &el);
+
+    let image = unsafe {
+        ClearColor(0.1, 0.2, 0.3, 1.0);
+        renderer.capture(256, 256).expect("Failed to capture the frame")
+    };
+
+    image.save("headless.png").expect("Failed to save the capture");
+    println!("saved headless.png");
+}
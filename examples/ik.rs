@@ -14,12 +14,15 @@ fn main() {
 
     /* default projection type is perspective */
     renderer.camera.set_projection(ProjectionType::Orthographic);
-    renderer.add_light(Light { position: vec3(0.0, 0.0, 3.0), color: Vec3::ONE })
+    renderer.add_light(Light { position: vec3(0.0, 0.0, 3.0), color: Vec3::ONE, ..Default::default() })
         .unwrap();
 
     /* we'll represent our player using a quad */
     let player_handle = renderer.add_mesh(Quad::new(Vec3::ONE * 0.1, Vec4::ONE).mesh())
         .unwrap();
+    let player_material = renderer.add_material(Material::default())
+        .unwrap();
+    renderer.meshes[player_handle].set_material(player_material);
 
     renderer.meshes[player_handle].position = Vec3::Z * 2.0; // so the player stays in front of everything
 
@@ -50,6 +53,7 @@ fn main() {
             s.update(&mut renderer);
         });
         
+        renderer.materials[player_material].set_color(vec3(0.5, 0.0, el.time.sin()));
         let player = &mut renderer.meshes[player_handle];
         let mp = el.event_handler.mouse_pos / el.event_handler.width * 2.0;
         let clamped_player_pos = {
@@ -77,12 +81,18 @@ fn main() {
         player_vel = player.position - old_pos;
         old_pos = player.position;
 
-        player.color = vec3(0.5, 0.0, el.time.sin());
         move_player(&el, &mut player.position);
     
         renderer.camera.update(lerp(renderer.camera.pos, player.position, 0.125), &el);
         renderer.camera.mouse_callback(el.event_handler.mouse_pos, &el.window);
 
+        /* the chain's bones and where it's reaching for, drawn on top of the quads */
+        renderer.debug.on_top = true;
+        for pair in segments.windows(2) {
+            renderer.debug.line(pair[0].pos, pair[1].pos, vec4(1.0, 1.0, 0.0, 1.0));
+        }
+        renderer.debug.wire_sphere(segments[segments.len()-1].pos, 0.05, vec4(1.0, 0.0, 0.0, 1.0));
+
         let frame = el.ui.frame(&mut el.window);
         frame.text("Hello, world! This is imgui.");
         frame.text(format!("p: {:.1}\nv: {:.3}", player.position, player_vel));
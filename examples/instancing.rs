@@ -0,0 +1,120 @@
+/*
+The same scene as the cull example, but every cuboid is an instance
+of a single mesh so the whole thing is one draw call
+*/
+
+use chaos_framework::*;
+use culler::Culler;
+use glfw::Key;
+
+fn main() {
+    let mut el = EventLoop::new(800, 600);
+    let mut renderer = Renderer::new();
+
+    el.window.glfw.set_swap_interval(SwapInterval::Sync(0));
+
+    unsafe {
+        Enable(DEPTH_TEST);
+        Enable(CULL_FACE);
+    }
+
+    /* the cuboids shadow each other, instances are drawn into the shadow map in one call too */
+    renderer.add_light(Light {
+        shadow: Some(ShadowSettings { far: 150.0, extent: 60.0, ..Default::default() }),
+        ..Light::directional(vec3(-0.3, -1.0, -0.2), Vec3::ONE)
+    }).unwrap();
+
+    let mut culler = Culler::new(&renderer);
+
+    let size = 30.0;
+    let mut cuboids = Cuboid::new(Vec3::ONE, Vec4::ONE).mesh().to_instanced();
+    for _ in 0..1024 {
+        let mut instance = Instance::new((rand_vec3() * 2.0 - 1.0) * size + vec3(0.0, size, 0.0));
+        instance.color = rand_vec4().truncate().extend(1.0);
+
+        cuboids.add_instance(instance);
+    }
+
+    let handle = renderer.add_instanced_mesh(cuboids).unwrap();
+    culler.add_instanced_mesh(handle);
+
+    renderer.post_process = Some(PostProcessSettings {
+        vignette: Some(VignetteSettings::default()),
+        ..Default::default()
+    });
+    let mut post_process = true;
+
+    /* F12 saves a screenshot, F10 starts and stops recording a gif */
+    let mut recorder = Recorder::new("captures");
+
+    let mut b = true;
+    let mut button_pressed = true;
+
+    while !el.window.should_close() {
+        el.update();
+        if b {
+            culler.update(&mut renderer, &el);
+        }
+        renderer.update();
+
+        renderer.camera.input(&el);
+        renderer.camera.mouse_callback(el.event_handler.mouse_pos, &el.window);
+        renderer.camera.update(renderer.camera.pos, &el);
+
+        let cuboids = &renderer.instanced_meshes[handle];
+
+        let frame = el.ui.frame(&mut el.window);
+        frame.text("hello, world!\nTIP: hold alt to toggle mouse mode");
+        frame.text(
+            format!("sent to gpu: {:?}\ntotal: {:?}", cuboids.visible_instances, cuboids.instances.len())
+        );
+        frame.text(format!("frametime: {:.1}ms", el.dt*1000.0));
+
+        if frame.checkbox("post processing", &mut post_process) {
+            renderer.post_process = if post_process {
+                Some(PostProcessSettings { vignette: Some(VignetteSettings::default()), ..Default::default() })
+            } else {
+                None
+            };
+        }
+
+        let mut view = DebugView::ALL.iter().position(|view| *view == renderer.debug_view).unwrap_or(0);
+        if frame.combo("view", &mut view, &DebugView::ALL, |view| view.name().into()) {
+            renderer.debug_view = DebugView::ALL[view];
+        }
+
+        let button_clicked = frame.button("pause culling");
+
+        if button_clicked && !button_pressed {
+            b = !b;
+            button_pressed = true;
+        } else if !button_clicked {
+            button_pressed = false;
+        }
+
+        if el.event_handler.key_just_pressed(Key::F10) {
+            if recorder.is_recording() {
+                if let Some(path) = recorder.stop(&mut el) {
+                    println!("Saved recording to {}", path.display());
+                }
+            } else if let Err(err) = recorder.start(&mut el, RecordingFormat::Gif, 30) {
+                println!("Failed to start recording: {}", err);
+            }
+        }
+
+        if el.is_key_down(Key::LeftAlt) {
+            el.window.set_cursor_mode(CursorMode::Normal);
+        } else {
+            el.window.set_cursor_mode(CursorMode::Disabled);
+        }
+
+        unsafe {
+            Clear(COLOR_BUFFER_BIT | DEPTH_BUFFER_BIT);
+            ClearColor(0.1, 0.2, 0.3, 1.0);
+
+            renderer.draw();
+            recorder.update(&mut el, &renderer);
+            el.ui.draw();
+        }
+    }
+}
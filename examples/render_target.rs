@@ -0,0 +1,80 @@
+/*
+A security camera: the scene is drawn into an off-screen target from a fixed
+point of view, then shown on a screen in the world and in an imgui window
+*/
+
+use chaos_framework::*;
+use glfw::Key;
+
+fn main() {
+    let mut el = EventLoop::new(800, 600);
+    let mut renderer = Renderer::new();
+
+    unsafe {
+        Enable(DEPTH_TEST);
+    }
+
+    renderer.add_light(Light::point(vec3(0.0, 5.0, 5.0), Vec3::ONE)).unwrap();
+
+    for i in 0..8 {
+        let mut cuboid = Cuboid::new(Vec3::ONE, Vec4::ONE).mesh();
+        cuboid.position = vec3(i as f32 * 2.0 - 8.0, 0.0, -5.0);
+        renderer.add_mesh(cuboid).unwrap();
+    }
+
+    let target = renderer.add_render_target(
+        RenderTarget::new(RenderTargetSettings { width: 256, height: 256, samples: 4, ..Default::default() }).unwrap()
+    ).unwrap();
+
+    let mut screen = Quad::new(vec3(4.0, 4.0, 1.0), Vec4::ONE).mesh();
+    screen.position = vec3(-2.0, 2.0, -10.0);
+    screen.material = renderer.add_material({
+        let mut material = Material::default();
+        material.set_diffuse_texture(renderer.render_target_texture(target, 0).unwrap(), &renderer);
+
+        material
+    });
+    let screen = renderer.add_mesh(screen).unwrap();
+
+    while !el.window.should_close() {
+        el.update();
+        renderer.update();
+
+        renderer.camera.input(&el);
+        renderer.camera.mouse_callback(el.event_handler.mouse_pos, &el.window);
+        renderer.camera.update(renderer.camera.pos, &el);
+
+        if el.is_key_down(Key::LeftAlt) {
+            el.window.set_cursor_mode(CursorMode::Normal);
+        } else {
+            el.window.set_cursor_mode(CursorMode::Disabled);
+        }
+
+        let frame = el.ui.frame(&mut el.window);
+        frame.window("security camera").build(|| {
+            let texture = renderer.render_targets[target].imgui_texture(0).unwrap();
+            Image::new(texture, [256.0, 256.0]).uv0([0.0, 1.0]).uv1([1.0, 0.0]).build(frame);
+        });
+
+        unsafe {
+            ClearColor(0.1, 0.2, 0.3, 1.0);
+
+            /* the security camera sweeps over the cuboids, the screen is hidden so it doesn't see itself */
+            let player_camera = renderer.camera;
+            let sweep = (el.time * 0.5).sin() * 6.0;
+            renderer.camera.pos = vec3(0.0, 6.0, 4.0);
+            renderer.camera.view = Mat4::look_at_rh(renderer.camera.pos, vec3(sweep, 0.0, -5.0), Vec3::Y);
+            renderer.camera.proj = Mat4::perspective_rh_gl(60.0f32.to_radians(), 1.0, 0.1, 100.0);
+            renderer.meshes[screen].hidden = true;
+
+            renderer.draw_to(target);
+
+            renderer.camera = player_camera;
+            renderer.meshes[screen].hidden = false;
+
+            Clear(COLOR_BUFFER_BIT | DEPTH_BUFFER_BIT);
+            renderer.draw();
+            el.ui.draw();
+        }
+    }
+}
@@ -0,0 +1,83 @@
+/*
+A tiny solar system: the planet orbits the sun, the moon orbits the planet and
+a light follows the moon, only by spinning the nodes they hang from.
+press P to move the moon over to the sun without it jumping
+*/
+
+use chaos_framework::*;
+use glfw::Key;
+
+fn main() {
+    let mut el = EventLoop::new(800, 600);
+    let mut renderer = Renderer::new();
+
+    unsafe {
+        Enable(DEPTH_TEST);
+    }
+
+    let sun_mesh = renderer.add_mesh(Sphere::new(16, 1.5, vec4(1.0, 0.8, 0.2, 1.0)).mesh()).unwrap();
+    let planet_mesh = renderer.add_mesh(Sphere::new(16, 0.6, vec4(0.2, 0.5, 1.0, 1.0)).mesh()).unwrap();
+    let moon_mesh = renderer.add_mesh(Sphere::new(12, 0.2, Vec4::ONE).mesh()).unwrap();
+    let light = renderer.add_light(Light::point(Vec3::ZERO, vec3(1.0, 0.9, 0.8))).unwrap();
+
+    let sun = renderer.add_node(
+        Node::new("sun", NodeContent::Mesh(sun_mesh)).with_transform(vec3(0.0, 0.0, -10.0), Quat::IDENTITY, Vec3::ONE)
+    ).unwrap();
+    // the orbits are empty nodes spinning in place, whatever hangs off them goes around
+    let planet_orbit = renderer.add_child_node(sun, Node::empty("planet orbit")).unwrap();
+    let planet = renderer.add_child_node(
+        planet_orbit,
+        Node::new("planet", NodeContent::Mesh(planet_mesh)).with_transform(vec3(5.0, 0.0, 0.0), Quat::IDENTITY, Vec3::ONE),
+    ).unwrap();
+    let moon_orbit = renderer.add_child_node(planet, Node::empty("moon orbit")).unwrap();
+    let moon = renderer.add_child_node(
+        moon_orbit,
+        Node::new("moon", NodeContent::Mesh(moon_mesh)).with_transform(vec3(1.5, 0.0, 0.0), Quat::IDENTITY, Vec3::ONE),
+    ).unwrap();
+    renderer.add_child_node(
+        moon,
+        Node::new("moon light", NodeContent::Light(light)).with_transform(vec3(0.0, 1.0, 0.0), Quat::IDENTITY, Vec3::ONE),
+    ).unwrap();
+
+    let mut moved = false;
+
+    while !el.window.should_close() {
+        el.update();
+
+        renderer.nodes[planet_orbit].rotate(Quat::from_rotation_y(el.dt * 0.5));
+        renderer.nodes[moon_orbit].rotate(Quat::from_rotation_y(el.dt * 2.0));
+
+        if el.is_key_down(Key::P) && !moved {
+            renderer.set_parent(moon, Some(sun));
+            moved = true;
+        }
+
+        renderer.update();
+
+        renderer.camera.input(&el);
+        renderer.camera.mouse_callback(el.event_handler.mouse_pos, &el.window);
+        renderer.camera.update(renderer.camera.pos, &el);
+
+        if el.is_key_down(Key::LeftAlt) {
+            el.window.set_cursor_mode(CursorMode::Normal);
+        } else {
+            el.window.set_cursor_mode(CursorMode::Disabled);
+        }
+
+        let frame = el.ui.frame(&mut el.window);
+        frame.window("scene").build(|| {
+            for (_, node) in renderer.nodes.iter() {
+                let position = node.world_position();
+                frame.text(format!("{}: {:.1} {:.1} {:.1}", node.name, position.x, position.y, position.z));
+            }
+        });
+
+        unsafe {
+            ClearColor(0.02, 0.02, 0.05, 1.0);
+            Clear(COLOR_BUFFER_BIT | DEPTH_BUFFER_BIT);
+
+            renderer.draw();
+            el.ui.draw();
+        }
+    }
+}
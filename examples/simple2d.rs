@@ -11,38 +11,52 @@ fn main() {
 
     /* default projection type is perspective */
     renderer.camera.set_projection(ProjectionType::Orthographic);
-    renderer.add_light(Light { position: vec3(0.0, 0.0, 1.0), color: Vec3::ONE })
-        .unwrap();
 
-    /* we'll represent our player using a quad */
-    let player_handle = renderer.add_mesh(Quad::new(Vec3::ONE * 0.1, Vec4::ONE).mesh())
-        .unwrap();
+    /* sprites are collected every frame and drawn together, unlit */
+    let mut batch = SpriteBatch::new();
+
+    /* we'll represent our player using a sprite */
+    let mut player = Sprite::new(Vec2::ZERO, Vec2::splat(0.1));
+    player.layer = 1;
 
     while !el.window.should_close() {
         el.update();
+        renderer.update();
 
-        /* we can modify the player by indexing into it in the renderer's meshes */
-        let player = &mut renderer.meshes[player_handle];
-        player.color = vec3(0.5, 0.0, el.time.sin());
+        player.color = vec4(0.5, 0.0, el.time.sin(), 1.0);
         move_player(&el, &mut player.position);
     
         renderer.camera.update(Vec3::ZERO, &el);
+
+        /* a checkerboard floor below the player */
+        for x in -10..10 {
+            for y in -10..10 {
+                let shade = if (x + y) % 2 == 0 { 0.25 } else { 0.3 };
+                let mut tile = Sprite::new(vec2(x as f32, y as f32) * 0.1, Vec2::splat(0.1));
+                tile.origin = Vec2::ZERO;
+                tile.color = vec4(shade, shade, shade + 0.1, 1.0);
+                batch.draw(tile);
+            }
+        }
+        batch.draw(player);
     
         let frame = el.ui.frame(&mut el.window);
         frame.text("hello, world! this is imgui");
+        frame.text(format!("sprites: {}", batch.len()));
 
         unsafe {
             Clear(COLOR_BUFFER_BIT | DEPTH_BUFFER_BIT);
             ClearColor(0.1, 0.2, 0.3, 1.0);
             
             renderer.draw();
+            batch.flush(&renderer);
             el.ui.draw();
         }
     }
 }
 
-fn move_player(el: &EventLoop, pos: &mut Vec3) {
-    let mut velocity = Vec3::ZERO; 
+fn move_player(el: &EventLoop, pos: &mut Vec2) {
+    let mut velocity = Vec2::ZERO; 
     let mut speed = 1.5;
 
     if el.is_key_down(Key::LeftShift) {
@@ -4,7 +4,7 @@ use std::time::Instant;
 
 use gl::{BlendFunc, Enable, Viewport, BLEND, DEPTH_TEST, ONE_MINUS_SRC_ALPHA, SRC_ALPHA};
 use glam::{vec2, Vec2};
-use glfw::{fail_on_errors, Glfw, GlfwReceiver, PWindow, WindowEvent};
+use glfw::{fail_on_errors, log_errors, Glfw, GlfwReceiver, PWindow, WindowEvent};
 use glfw::{Action, Context, Key};
 
 use crate::{EventHandler, Imgui};
@@ -20,21 +20,54 @@ pub struct EventLoop {
     pub time: f32,
 
     pub timescale: f32,
+    /// when set, every frame advances time by this much no matter how long it took,
+    /// so recordings play back at the right speed
+    pub fixed_dt: Option<f32>,
 }
 
 impl EventLoop {
     pub fn new(w: u32, h: u32) -> Self {
         let mut glfw = glfw::init(fail_on_errors!()).unwrap();
-        
+
+        Self::window_hints(&mut glfw);
+
+        let (window, events) = glfw.create_window(w, h, "Hello this is window", glfw::WindowMode::Windowed)
+        .expect("Failed to create GLFW window.");
+
+        Self::with_window(glfw, window, events, w, h)
+    }
+
+    /// an invisible window for tests and offline rendering. draw into a `RenderTarget`
+    /// (or use `Renderer::capture`), the contents of a hidden window aren't guaranteed.
+    /// glfw still needs a display server, on a linux machine without a gpu run under
+    /// xvfb with mesa's software driver (LIBGL_ALWAYS_SOFTWARE=1). if no native context
+    /// can be created an OSMesa one is tried instead
+    pub fn headless(w: u32, h: u32) -> Self {
+        let mut glfw = glfw::init(log_errors!()).expect("Failed to initialize GLFW, is there a display (or xvfb) available?");
+
+        Self::window_hints(&mut glfw);
+        glfw.window_hint(glfw::WindowHint::Visible(false));
+
+        let (window, events) = glfw.create_window(w, h, "headless", glfw::WindowMode::Windowed)
+            .or_else(|| {
+                glfw.window_hint(glfw::WindowHint::ContextCreationApi(glfw::ContextCreationApi::OsMesa));
+                glfw.create_window(w, h, "headless", glfw::WindowMode::Windowed)
+            })
+            .expect("Failed to create a headless GLFW window.");
+
+        Self::with_window(glfw, window, events, w, h)
+    }
+
+    fn window_hints(glfw: &mut Glfw) {
         // glfw.window_hint(glfw::WindowHint::TransparentFramebuffer(true));
         glfw.window_hint(glfw::WindowHint::Decorated(true));
         glfw.window_hint(glfw::WindowHint::DoubleBuffer(true));
         glfw.window_hint(glfw::WindowHint::ContextVersionMajor(3));
         glfw.window_hint(glfw::WindowHint::ContextVersionMinor(3));
         // glfw.window_hint(glfw::WindowHint::Samples(Some(4)));
+    }
 
-        let (mut window, events) = glfw.create_window(w, h, "Hello this is window", glfw::WindowMode::Windowed)
-        .expect("Failed to create GLFW window.");
+    fn with_window(glfw: Glfw, mut window: PWindow, events: GlfwReceiver<(f64, WindowEvent)>, w: u32, h: u32) -> Self {
     
         let ui = Imgui::new(&mut window);
 
@@ -50,7 +83,8 @@ impl EventLoop {
         
         unsafe {
             BlendFunc(SRC_ALPHA, ONE_MINUS_SRC_ALPHA);
-            Enable(BLEND | DEPTH_TEST);
+            Enable(BLEND);
+            Enable(DEPTH_TEST);
         }
     
         let mut event_handler = EventHandler::new();
@@ -66,6 +100,7 @@ impl EventLoop {
             dt: 0.0,
             time: 0.0,
             timescale: 1.0,
+            fixed_dt: None,
         }
     }
 
@@ -74,7 +109,7 @@ impl EventLoop {
     }
 
     pub fn update(&mut self) {
-        self.dt = self.now.elapsed().as_secs_f32() * self.timescale;
+        self.dt = self.fixed_dt.unwrap_or(self.now.elapsed().as_secs_f32()) * self.timescale;
         self.time += self.dt;
         self.now = std::time::Instant::now();
 
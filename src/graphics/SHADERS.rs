@@ -1,15 +1,262 @@
 
+/*
+chunks that any shader can pull in with #include "name.glsl", see preprocessor.rs.
+LIGHT_BUFFER_SIZE and MAX_SHADOW_MAPS are always defined by the preprocessor
+*/
+pub static SHADER_CHUNKS: &[(&str, &str)] = &[
+    ("camera.glsl", CAMERA_GLSL),
+    ("lights.glsl", LIGHTS_GLSL),
+    ("shadows.glsl", SHADOWS_GLSL),
+    ("lighting.glsl", LIGHTING_GLSL),
+];
+
+pub const CAMERA_GLSL: &str = r#"
+layout(std140) uniform Camera {
+    mat4 view;
+    mat4 proj;
+    vec4 viewPos;
+    ivec4 debugView;   // x: DebugView
+};
+"#;
+
+pub const LIGHTS_GLSL: &str = r#"
+// how many lights the loop goes through, can be lowered per shader
+#ifndef MAX_LIGHTS
+#define MAX_LIGHTS LIGHT_BUFFER_SIZE
+#endif
+
+const int DIRECTIONAL = 0;
+const int POINT = 1;
+const int SPOT = 2;
+
+// filled once per frame by the renderer, mirrors LightsBlock in uniform_buffer.rs
+layout(std140) uniform Lights {
+    vec4 ambient;
+    ivec4 lightCount;               // x: number of lights
+    ivec4 shadowSlot[MAX_SHADOW_MAPS];    // x: index of the light the map belongs to, -1 if unused, y: 1 for a cubemap
+    vec4 shadowOrigin[MAX_SHADOW_MAPS];
+    vec4 shadowParams[MAX_SHADOW_MAPS];   // x: bias, y: pcf radius, z: far plane, w: texel size
+    mat4 shadowMatrix[MAX_SHADOW_MAPS];
+    vec4 lightPos[LIGHT_BUFFER_SIZE];         // xyz: position, w: range
+    vec4 lightColor[LIGHT_BUFFER_SIZE];       // rgb: color * intensity, w: kind
+    vec4 lightDirection[LIGHT_BUFFER_SIZE];   // xyz: direction, w: cosine of the outer cone
+    vec4 lightAttenuation[LIGHT_BUFFER_SIZE]; // xyz: constant, linear, quadratic, w: cosine of the inner cone
+};
+"#;
+
+pub const SHADOWS_GLSL: &str = r#"
+#include "camera.glsl"
+#include "lights.glsl"
+
+uniform samplerCube shadowMaps[MAX_SHADOW_MAPS];
+uniform sampler2D flatShadowMaps[MAX_SHADOW_MAPS];
+
+// sampler arrays can only be indexed by constants in glsl 330, so the slots are spelled out
+#if MAX_SHADOW_MAPS > 8
+#error "sampleCubeShadowMap and sampleFlatShadowMap only go up to 8 shadow maps"
+#endif
+
+float sampleCubeShadowMap(int slot, vec3 dir) {
+#if MAX_SHADOW_MAPS > 1
+    if (slot == 1) return texture(shadowMaps[1], dir).r;
+#endif
+#if MAX_SHADOW_MAPS > 2
+    if (slot == 2) return texture(shadowMaps[2], dir).r;
+#endif
+#if MAX_SHADOW_MAPS > 3
+    if (slot == 3) return texture(shadowMaps[3], dir).r;
+#endif
+#if MAX_SHADOW_MAPS > 4
+    if (slot == 4) return texture(shadowMaps[4], dir).r;
+#endif
+#if MAX_SHADOW_MAPS > 5
+    if (slot == 5) return texture(shadowMaps[5], dir).r;
+#endif
+#if MAX_SHADOW_MAPS > 6
+    if (slot == 6) return texture(shadowMaps[6], dir).r;
+#endif
+#if MAX_SHADOW_MAPS > 7
+    if (slot == 7) return texture(shadowMaps[7], dir).r;
+#endif
+    return texture(shadowMaps[0], dir).r;
+}
+
+float sampleFlatShadowMap(int slot, vec2 uv) {
+#if MAX_SHADOW_MAPS > 1
+    if (slot == 1) return texture(flatShadowMaps[1], uv).r;
+#endif
+#if MAX_SHADOW_MAPS > 2
+    if (slot == 2) return texture(flatShadowMaps[2], uv).r;
+#endif
+#if MAX_SHADOW_MAPS > 3
+    if (slot == 3) return texture(flatShadowMaps[3], uv).r;
+#endif
+#if MAX_SHADOW_MAPS > 4
+    if (slot == 4) return texture(flatShadowMaps[4], uv).r;
+#endif
+#if MAX_SHADOW_MAPS > 5
+    if (slot == 5) return texture(flatShadowMaps[5], uv).r;
+#endif
+#if MAX_SHADOW_MAPS > 6
+    if (slot == 6) return texture(flatShadowMaps[6], uv).r;
+#endif
+#if MAX_SHADOW_MAPS > 7
+    if (slot == 7) return texture(flatShadowMaps[7], uv).r;
+#endif
+    return texture(flatShadowMaps[0], uv).r;
+}
+
+// the shadow maps store the distance to shadowOrigin divided by the far plane
+float shadowFactor(int slot, vec3 fragPos) {
+    vec3 fromOrigin = fragPos - shadowOrigin[slot].xyz;
+    float currentDepth = length(fromOrigin);
+    float bias = shadowParams[slot].x;
+    int pcf = int(shadowParams[slot].y);
+    float far = shadowParams[slot].z;
+    if (currentDepth > far) {
+        return 0.0;
+    }
+
+    float shadow = 0.0;
+    int samples = 0;
+
+    if (shadowSlot[slot].y == 1) {
+        float diskRadius = (1.0 + length(viewPos.xyz - fragPos) / far) / 50.0;
+        for (int x = -pcf; x <= pcf; ++x) {
+            for (int y = -pcf; y <= pcf; ++y) {
+                for (int z = -pcf; z <= pcf; ++z) {
+                    float closestDepth = sampleCubeShadowMap(slot, fromOrigin + vec3(x, y, z) * diskRadius) * far;
+                    shadow += currentDepth - bias > closestDepth ? 1.0 : 0.0;
+                    samples++;
+                }
+            }
+        }
+    } else {
+        vec4 lightSpace = shadowMatrix[slot] * vec4(fragPos, 1.0);
+        vec3 projected = lightSpace.xyz / lightSpace.w * 0.5 + 0.5;
+        if (projected.z > 1.0) {
+            return 0.0;
+        }
+
+        float texel = shadowParams[slot].w;
+        for (int x = -pcf; x <= pcf; ++x) {
+            for (int y = -pcf; y <= pcf; ++y) {
+                float closestDepth = sampleFlatShadowMap(slot, projected.xy + vec2(x, y) * texel) * far;
+                shadow += currentDepth - bias > closestDepth ? 1.0 : 0.0;
+                samples++;
+            }
+        }
+    }
+
+    return shadow / float(samples);
+}
+
+// how much of the light is blocked at fragPos, 0 if the light has no shadow map
+float lightShadow(int light, vec3 fragPos) {
+    float shadow = 0.0;
+    for (int s = 0; s < MAX_SHADOW_MAPS; ++s) {
+        if (shadowSlot[s].x == light) {
+            shadow = shadowFactor(s, fragPos);
+        }
+    }
+    return shadow;
+}
+"#;
+
+pub const LIGHTING_GLSL: &str = r#"
+#include "camera.glsl"
+#include "lights.glsl"
+#include "shadows.glsl"
+
+// attenuation, range falloff and spot cone of a light at fragPos. lightDir points towards the light
+float lightAttenuationAt(int i, vec3 fragPos, out vec3 lightDir) {
+    int kind = int(lightColor[i].w);
+
+    if (kind == DIRECTIONAL) {
+        lightDir = normalize(-lightDirection[i].xyz);
+        return 1.0;
+    }
+
+    vec3 toLight = lightPos[i].xyz - fragPos;
+    float distance = length(toLight);
+    lightDir = toLight / distance;
+
+    vec3 att = lightAttenuation[i].xyz;
+    float attenuation = 1.0 / (att.x + att.y * distance + att.z * distance * distance);
+
+    float falloff = clamp(1.0 - pow(distance / lightPos[i].w, 4.0), 0.0, 1.0);
+    attenuation *= falloff * falloff;
+
+    if (kind == SPOT) {
+        float theta = dot(lightDir, normalize(-lightDirection[i].xyz));
+        float inner = lightAttenuation[i].w;
+        float outer = lightDirection[i].w;
+        attenuation *= clamp((theta - outer) / max(inner - outer, 0.0001), 0.0, 1.0);
+    }
+
+    return attenuation;
+}
+
+// ambient plus phong diffuse and specular from every light in the Lights block
+vec3 computeLighting(vec3 albedo, vec3 normal, vec3 fragPos, bool receiveShadows) {
+    vec3 specularStrength = vec3(0.5);
+    vec3 viewDir = normalize(viewPos.xyz - fragPos);
+
+    vec3 result = ambient.rgb * albedo;
+
+    int count = min(lightCount.x, MAX_LIGHTS);
+    for (int i = 0; i < count; ++i) {
+        vec3 lightDir;
+        float attenuation = lightAttenuationAt(i, fragPos, lightDir);
+        float shadow = receiveShadows ? lightShadow(i, fragPos) : 0.0;
+
+        float diff = max(dot(normal, lightDir), 0.0);
+        vec3 diffuse = diff * lightColor[i].rgb * attenuation;
+
+        vec3 reflectDir = reflect(-lightDir, normal);
+
+        float spec = pow(max(dot(viewDir, reflectDir), 0.0), 32);
+        vec3 specular = specularStrength * spec * lightColor[i].rgb * attenuation;
+
+        result += (1.0 - shadow) * (diffuse + specular) * albedo;
+    }
+
+    return result;
+}
+"#;
+
+/// compiled with HAS_INSTANCING for instanced meshes and HAS_SKINNING for skeletal meshes
 pub static DEFAULT_VS: &str = r#"
 #version 330 core
+#include "camera.glsl"
+
+#if defined(HAS_SKINNING) && defined(HAS_INSTANCING)
+#error skinned meshes can't be instanced
+#endif
+
+#ifdef HAS_SKINNING
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aNormal;
+layout (location = 2) in vec2 aTexCoord;
+layout (location = 3) in ivec4 aBoneIds;
+layout (location = 4) in vec4 aWeights;
+
+const int MAX_BONES = 100;
+uniform mat4 finalBonesMatrices[MAX_BONES];
+#else
 layout (location = 0) in vec3 aPos;
 layout (location = 1) in vec4 aColor;
 layout (location = 2) in vec2 aTexCoord;
 layout (location = 3) in vec3 aNormal;
+#endif
 
+#ifdef HAS_INSTANCING
+layout (location = 4) in mat4 aModel; // takes up locations 4 through 7
+layout (location = 8) in vec4 aInstanceColor;
+#else
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 proj;
-uniform vec3 color;
+uniform vec4 color; // tint, multiplied with the vertex color
+#endif
 
 out vec4 fColor;
 out vec3 Normal;
@@ -17,32 +264,99 @@ out vec3 FragPos;
 out vec2 TexCoord; // Pass texture coordinates to the fragment shader
 
 void main() {
-    gl_Position = proj * view * model * vec4(aPos, 1.0);
-    fColor = vec4(color, 1.0);
+#ifdef HAS_INSTANCING
+    mat4 modelMatrix = aModel;
+    fColor = aInstanceColor;
+#else
+    mat4 modelMatrix = model;
+    fColor = color;
+#endif
+
+#ifndef HAS_SKINNING
+    fColor *= aColor;
+#endif
+
+#ifdef HAS_SKINNING
+    mat4 boneTransform = mat4(0.0);
+    for (int i = 0; i < 4; i++) {
+        if (aBoneIds[i] != -1) {
+            boneTransform += finalBonesMatrices[aBoneIds[i]] * aWeights[i];
+        }
+    }
+    vec4 localPos = boneTransform * vec4(aPos, 1.0);
+#else
+    vec4 localPos = vec4(aPos, 1.0);
+#endif
+
+    gl_Position = proj * view * modelMatrix * localPos;
     TexCoord = aTexCoord; // Pass texture coordinates
-    FragPos = vec3(model * vec4(aPos, 1.0));
-    Normal = mat3(transpose(inverse(model))) * aNormal;  
+    FragPos = vec3(modelMatrix * localPos);
+    Normal = mat3(transpose(inverse(modelMatrix))) * aNormal;
 }
 "#;
 
 pub static DEFAULT_FS: &str = r#"
 #version 330 core
+#include "lighting.glsl"
+
 out vec4 FragColor;
 
 in vec4 fColor;
 in vec2 TexCoord;
 in vec3 Normal;
-in vec3 FragPos;  
-
-uniform vec3 lightColor[256];
-uniform vec3 lightPos[256];
-uniform vec3 viewPos;
+in vec3 FragPos;
 
 uniform int has_texture;
-uniform int num_lights;
+uniform int receive_shadows;
 
 uniform sampler2D texture1;
 
+// the skybox unless the material sets its own
+uniform samplerCube environment;
+uniform float reflectivity;
+
+// pixels less opaque than this are discarded, for the alpha tested queue
+uniform float alpha_cutoff;
+
+// matches DebugView::id
+#define VIEW_NORMALS 2
+#define VIEW_UV_CHECKER 3
+#define VIEW_FLAT_LIGHTING 4
+#define VIEW_DEPTH 5
+#define VIEW_OVERDRAW 6
+#define VIEW_WIREFRAME_LINES 7
+
+vec4 debugColor(int mode, vec4 albedo, vec3 norm) {
+    if (mode == VIEW_NORMALS) {
+        return vec4(norm * 0.5 + 0.5, 1.0);
+    }
+    if (mode == VIEW_UV_CHECKER) {
+        vec2 cell = floor(TexCoord * 8.0);
+        float checker = mod(cell.x + cell.y, 2.0);
+        return vec4(mix(vec3(0.15), vec3(0.85), checker) * vec3(0.6 + 0.4 * fract(TexCoord), 1.0), 1.0);
+    }
+    if (mode == VIEW_FLAT_LIGHTING) {
+        return albedo;
+    }
+    if (mode == VIEW_DEPTH) {
+        float depth = gl_FragCoord.z;
+        // perspective depth isn't linear, orthographic depth already is
+        if (proj[3][3] == 0.0) {
+            float near = proj[3][2] / (proj[2][2] - 1.0);
+            float far = proj[3][2] / (proj[2][2] + 1.0);
+            float z = depth * 2.0 - 1.0;
+            depth = (2.0 * near * far) / (far + near - z * (far - near)) / far;
+        }
+        // sqrt spreads out the nearby range
+        return vec4(vec3(1.0 - sqrt(depth)), 1.0);
+    }
+    if (mode == VIEW_OVERDRAW) {
+        // added up by the blending
+        return vec4(0.1, 0.04, 0.02, 1.0);
+    }
+    return vec4(0.1, 1.0, 0.3, 1.0);
+}
+
 void main()
 {
     vec4 texColor = fColor;
@@ -51,93 +365,545 @@ void main()
        texColor = texture(texture1, TexCoord) * fColor;
     }
 
-    vec3 ambientStrength = vec3(0.1); 
-    vec3 specularStrength = vec3(0.5);
+    if (texColor.a < alpha_cutoff) discard;
 
-    vec3 result = vec3(0.0);
+    vec3 norm = normalize(Normal);
+    if (debugView.x != 0) {
+        FragColor = debugColor(debugView.x, texColor, norm);
+        return;
+    }
 
-    for (int i = 0; i < num_lights; ++i) {
-        vec3 lightDir = normalize(lightPos[i] - FragPos); 
-        float distance = length(lightPos[i] - FragPos);
+    vec3 result = computeLighting(texColor.rgb, norm, FragPos, receive_shadows == 1);
 
-        float constant_attenuation = 1.0;
-        float linear_attenuation = 0.045;
-        float quadratic_attenuation = 0.016;
+    if (reflectivity > 0.0) {
+        vec3 incident = normalize(FragPos - viewPos.xyz);
+        vec3 reflected = texture(environment, reflect(incident, norm)).rgb;
+        result = mix(result, reflected, reflectivity);
+    }
 
-        float attenuation = 1.0 / (constant_attenuation + linear_attenuation * distance + quadratic_attenuation * distance * distance);
+    FragColor = vec4(result, texColor.a);
+}
+"#;
 
-        vec3 norm = normalize(Normal);
-        float diff = max(dot(norm, lightDir), 0.0);
-        vec3 diffuse = diff * lightColor[i] * attenuation;
+// a unit cube around the camera built from gl_VertexID as a 14 vertex triangle strip.
+// w is used as z so the cube always ends up on the far plane
+pub static SKYBOX_VS: &str = r#"
+#version 330 core
+#include "camera.glsl"
 
-        vec3 viewDir = normalize(viewPos - FragPos);
-        vec3 reflectDir = reflect(-lightDir, norm); 
+out vec3 direction;
 
-        float spec = pow(max(dot(viewDir, reflectDir), 0.0), 32);
-        vec3 specular = specularStrength * spec * lightColor[i] * attenuation;
+void main() {
+    int b = 1 << gl_VertexID;
+    direction = vec3((0x287a & b) != 0, (0x02af & b) != 0, (0x31e3 & b) != 0) * 2.0 - 1.0;
+
+    vec4 pos = proj * mat4(mat3(view)) * vec4(direction, 1.0);
+    gl_Position = pos.xyww;
+}
+"#;
 
-        result += ((ambientStrength / num_lights) + diffuse + specular) * texColor.rgb;
+pub static SKYBOX_FS: &str = r#"
+#version 330 core
+in vec3 direction;
+out vec4 FragColor;
+
+uniform samplerCube skybox;
+
+void main() {
+    FragColor = vec4(texture(skybox, direction).rgb, 1.0);
+}
+"#;
+
+// single scattering ray marched per pixel, drawn on the skybox cube. the math
+// matches AtmosphereSettings::sky_color
+pub static ATMOSPHERE_FS: &str = r#"
+#version 330 core
+in vec3 direction;
+out vec4 FragColor;
+
+uniform vec3 sunDirection;
+uniform float sunIntensity;
+uniform vec3 rayleighCoefficient;
+uniform float mieCoefficient;
+uniform float mieDirection;
+uniform float rayleighHeight;
+uniform float mieHeight;
+uniform float planetRadius;
+uniform float atmosphereRadius;
+
+#define PI 3.14159265
+#define PRIMARY_STEPS 16
+#define SECONDARY_STEPS 8
+
+// near and far distance along the ray, near > far if it misses
+vec2 raySphere(vec3 origin, vec3 dir, float radius) {
+    float b = dot(origin, dir);
+    float c = dot(origin, origin) - radius * radius;
+    float d = b * b - c;
+    if (d < 0.0) return vec2(1e20, -1e20);
+    d = sqrt(d);
+    return vec2(-b - d, -b + d);
+}
+
+vec2 opticalDepth(vec3 origin, vec3 dir, float len, int steps) {
+    float stepSize = len / float(steps);
+    vec2 depth = vec2(0.0);
+    for (int i = 0; i < steps; i++) {
+        float height = length(origin + dir * stepSize * (float(i) + 0.5)) - planetRadius;
+        depth += vec2(exp(-height / rayleighHeight), exp(-height / mieHeight)) * stepSize;
     }
+    return depth;
+}
 
-    FragColor = vec4(result, texColor.a);
+vec3 extinction(vec2 depth) {
+    return exp(-(rayleighCoefficient * depth.x + mieCoefficient * depth.y));
+}
+
+void main() {
+    vec3 dir = normalize(direction);
+    vec3 origin = vec3(0.0, planetRadius + 1.0, 0.0);
+
+    vec2 atmosphere = raySphere(origin, dir, atmosphereRadius);
+    if (atmosphere.x > atmosphere.y) discard;
+
+    vec2 ground = raySphere(origin, dir, planetRadius);
+    bool hitsGround = ground.x <= ground.y && ground.x > 0.0;
+    float start = max(atmosphere.x, 0.0);
+    float end = hitsGround ? min(atmosphere.y, ground.x) : atmosphere.y;
+    float stepSize = (end - start) / float(PRIMARY_STEPS);
+
+    vec3 totalRayleigh = vec3(0.0);
+    vec3 totalMie = vec3(0.0);
+    vec2 depth = vec2(0.0);
+
+    for (int i = 0; i < PRIMARY_STEPS; i++) {
+        vec3 pos = origin + dir * (start + stepSize * (float(i) + 0.5));
+        float height = length(pos) - planetRadius;
+        vec2 stepDepth = vec2(exp(-height / rayleighHeight), exp(-height / mieHeight)) * stepSize;
+        depth += stepDepth;
+
+        vec2 sunGround = raySphere(pos, sunDirection, planetRadius);
+        if (sunGround.x <= sunGround.y && sunGround.x > 0.0) continue;
+
+        vec2 sunDepth = opticalDepth(pos, sunDirection, raySphere(pos, sunDirection, atmosphereRadius).y, SECONDARY_STEPS);
+        vec3 attenuation = extinction(depth + sunDepth);
+        totalRayleigh += stepDepth.x * attenuation;
+        totalMie += stepDepth.y * attenuation;
+    }
+
+    float mu = dot(dir, sunDirection);
+    float g = mieDirection;
+    float phaseRayleigh = 3.0 / (16.0 * PI) * (1.0 + mu * mu);
+    float phaseMie = 3.0 / (8.0 * PI) * ((1.0 - g * g) * (1.0 + mu * mu))
+        / ((2.0 + g * g) * pow(1.0 + g * g - 2.0 * mu * g, 1.5));
+
+    vec3 color = sunIntensity * (phaseRayleigh * rayleighCoefficient * totalRayleigh + phaseMie * mieCoefficient * totalMie);
+
+    // the sun itself, dimmed by the air in front of it
+    if (!hitsGround) {
+        color += sunIntensity * extinction(depth) * smoothstep(0.9997, 0.9999, mu);
+    }
+
+    FragColor = vec4(color, 1.0);
 }
 "#;
 
+// unlit, used by SpriteBatch
+pub static SPRITE_VS: &str = r#"
+#version 330 core
+#include "camera.glsl"
 
-pub static RUSSIMP_VS: &str = r#"
-#version 430 core
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec2 aTexCoord;
+layout (location = 2) in vec4 aColor;
 
-layout(location = 0) in vec3 pos;
-layout(location = 1) in vec3 norm;
-layout(location = 2) in vec2 tex;
-layout(location = 3) in ivec4 boneIds; 
-layout(location = 4) in vec4 weights;
+out vec2 TexCoord;
+out vec4 fColor;
 
-uniform mat4 proj;
-uniform mat4 view;
-uniform mat4 model;
-uniform vec3 color;
+void main() {
+    TexCoord = aTexCoord;
+    fColor = aColor;
+    gl_Position = proj * view * vec4(aPos, 1.0);
+}
+"#;
+
+pub static SPRITE_FS: &str = r#"
+#version 330 core
+in vec2 TexCoord;
+in vec4 fColor;
+out vec4 FragColor;
+
+uniform sampler2D sprite;
+
+void main() {
+    FragColor = texture(sprite, TexCoord) * fColor;
+}
+"#;
+
+// glyphs from a font atlas, `transform` takes them straight to clip space
+pub static TEXT_VS: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec2 aTexCoord;
+
+out vec2 TexCoord;
+
+uniform mat4 transform;
+
+void main() {
+    TexCoord = aTexCoord;
+    gl_Position = transform * vec4(aPos, 1.0);
+}
+"#;
+
+pub static TEXT_FS: &str = r#"
+#version 330 core
+in vec2 TexCoord;
+out vec4 FragColor;
+
+uniform sampler2D glyphs;
+uniform vec4 color;
+uniform int sdf;
+
+void main() {
+    float alpha = texture(glyphs, TexCoord).a;
+
+    // the outline is at 0.5, blurred over about a pixel on screen
+    if (sdf == 1) {
+        float width = fwidth(alpha);
+        alpha = smoothstep(0.5 - width, 0.5 + width, alpha);
+    }
+
+    if (alpha < 0.01) discard;
+    FragColor = vec4(color.rgb, color.a * alpha);
+}
+"#;
+
+// colored lines for DebugDraw
+pub static DEBUG_VS: &str = r#"
+#version 330 core
+#include "camera.glsl"
+
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec4 aColor;
+
+out vec4 fColor;
+
+void main() {
+    fColor = aColor;
+    gl_Position = proj * view * vec4(aPos, 1.0);
+}
+"#;
+
+pub static DEBUG_FS: &str = r#"
+#version 330 core
+in vec4 fColor;
+out vec4 FragColor;
+
+void main() {
+    FragColor = fColor;
+}
+"#;
+
+/// compiled with HAS_INSTANCING for instanced meshes and HAS_SKINNING for skeletal meshes
+pub static SHADOW_VS: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 aPos;
+
+#if defined(HAS_SKINNING) && defined(HAS_INSTANCING)
+#error skinned meshes can't be instanced
+#endif
+
+#ifdef HAS_SKINNING
+layout (location = 3) in ivec4 aBoneIds;
+layout (location = 4) in vec4 aWeights;
 
 const int MAX_BONES = 100;
 uniform mat4 finalBonesMatrices[MAX_BONES];
+#endif
+
+#ifdef HAS_INSTANCING
+layout (location = 4) in mat4 aModel;
+#else
+uniform mat4 model;
+#endif
+
+uniform mat4 lightSpace;
 
-out vec2 TexCoord;
-out vec3 Normal;
 out vec3 FragPos;
-out vec4 fColor;
 
-void main()
-{
+void main() {
+#ifdef HAS_SKINNING
     mat4 boneTransform = mat4(0.0);
     for (int i = 0; i < 4; i++) {
-        if (boneIds[i] != -1) {
-            boneTransform += finalBonesMatrices[boneIds[i]] * weights[i];
+        if (aBoneIds[i] != -1) {
+            boneTransform += finalBonesMatrices[aBoneIds[i]] * aWeights[i];
         }
     }
-    
-    vec4 f_pos = boneTransform * vec4(pos, 1.0);
-    gl_Position = proj * view * model * f_pos;
+    vec4 localPos = boneTransform * vec4(aPos, 1.0);
+#else
+    vec4 localPos = vec4(aPos, 1.0);
+#endif
+
+#ifdef HAS_INSTANCING
+    FragPos = vec3(aModel * localPos);
+#else
+    FragPos = vec3(model * localPos);
+#endif
+    gl_Position = lightSpace * vec4(FragPos, 1.0);
+}
+"#;
 
-    TexCoord = tex;
-    fColor = vec4(color, 1.0);
-    FragPos = vec3(model * f_pos);
+// stores the linear distance to the light so point lights can use a cubemap
+pub static SHADOW_FS: &str = r#"
+#version 330 core
+in vec3 FragPos;
 
-    // Correcting the normal transformation
-    Normal = mat3(transpose(inverse(model))) * norm;  
+uniform vec3 lightPos;
+uniform float far_plane;
+
+void main() {
+    gl_FragDepth = length(FragPos - lightPos) / far_plane;
 }
+"#;
+
+// a single triangle covering the screen, drawn with 3 vertices and no buffers
+pub static FULLSCREEN_VS: &str = r#"
+#version 330 core
+out vec2 uv;
 
+void main() {
+    uv = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+}
 "#;
 
-use crate::Shader;
+// keeps only what is brighter than the threshold, the start of bloom
+pub static POST_BRIGHT_FS: &str = r#"
+#version 330 core
+in vec2 uv;
+out vec4 FragColor;
+
+uniform sampler2D image;
+uniform float threshold;
+
+void main() {
+    vec3 color = texture(image, uv).rgb;
+    float brightness = max(color.r, max(color.g, color.b));
+    FragColor = vec4(color * max(brightness - threshold, 0.0) / max(brightness, 0.0001), 1.0);
+}
+"#;
+
+// one direction of a separable 9 tap gaussian blur
+pub static POST_BLUR_FS: &str = r#"
+#version 330 core
+in vec2 uv;
+out vec4 FragColor;
+
+uniform sampler2D image;
+uniform vec2 direction; // texel size along the blurred axis
+
+const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+void main() {
+    vec3 result = texture(image, uv).rgb * weights[0];
+    for (int i = 1; i < 5; ++i) {
+        result += texture(image, uv + direction * float(i)).rgb * weights[i];
+        result += texture(image, uv - direction * float(i)).rgb * weights[i];
+    }
+    FragColor = vec4(result, 1.0);
+}
+"#;
+
+// bloom, exposure, tonemapping, gamma, color grading and vignette in one pass
+pub static POST_COMPOSITE_FS: &str = r#"
+#version 330 core
+in vec2 uv;
+out vec4 FragColor;
+
+const int TONEMAP_NONE = 0;
+const int TONEMAP_REINHARD = 1;
+const int TONEMAP_ACES = 2;
+
+uniform sampler2D scene;
+uniform sampler2D bloom;
+uniform sampler3D lut;
+
+uniform float exposure;
+uniform int tonemapping;
+uniform float gamma;
+
+uniform int use_bloom;
+uniform float bloom_intensity;
+
+uniform int use_lut;
+uniform float lut_size;
+uniform float lut_intensity;
+
+uniform int use_vignette;
+uniform float vignette_intensity;
+uniform float vignette_radius;
+uniform float vignette_softness;
+
+// krzysztof narkowicz's fit of the aces filmic curve
+vec3 aces(vec3 x) {
+    const float a = 2.51;
+    const float b = 0.03;
+    const float c = 2.43;
+    const float d = 0.59;
+    const float e = 0.14;
+    return (x * (a * x + b)) / (x * (c * x + d) + e);
+}
+
+void main() {
+    vec3 color = texture(scene, uv).rgb;
+
+    if (use_bloom == 1) {
+        color += texture(bloom, uv).rgb * bloom_intensity;
+    }
+
+    color *= exposure;
+
+    if (tonemapping == TONEMAP_REINHARD) {
+        color = color / (color + 1.0);
+    } else if (tonemapping == TONEMAP_ACES) {
+        color = aces(color);
+    }
+    color = clamp(color, 0.0, 1.0);
+
+    color = pow(color, vec3(1.0 / gamma));
+
+    // luts are authored for gamma corrected colors. the half texel offset samples texel centers
+    if (use_lut == 1) {
+        vec3 coord = color * ((lut_size - 1.0) / lut_size) + 0.5 / lut_size;
+        color = mix(color, texture(lut, coord).rgb, lut_intensity);
+    }
+
+    if (use_vignette == 1) {
+        float distance = length(uv - 0.5) * 1.41421;
+        float vignette = 1.0 - smoothstep(vignette_radius - vignette_softness, vignette_radius, distance);
+        color *= mix(1.0, vignette, vignette_intensity);
+    }
+
+    FragColor = vec4(color, 1.0);
+}
+"#;
+
+// fxaa in the style of the original nvidia console version, on gamma corrected colors
+pub static POST_FXAA_FS: &str = r#"
+#version 330 core
+in vec2 uv;
+out vec4 FragColor;
+
+uniform sampler2D image;
+uniform vec2 texel;
+
+const float REDUCE_MIN = 1.0 / 128.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float SPAN_MAX = 8.0;
+
+float luma(vec3 color) {
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+void main() {
+    float lumaNW = luma(texture(image, uv + vec2(-1.0, -1.0) * texel).rgb);
+    float lumaNE = luma(texture(image, uv + vec2(1.0, -1.0) * texel).rgb);
+    float lumaSW = luma(texture(image, uv + vec2(-1.0, 1.0) * texel).rgb);
+    float lumaSE = luma(texture(image, uv + vec2(1.0, 1.0) * texel).rgb);
+    float lumaM = luma(texture(image, uv).rgb);
+
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    vec2 dir = vec2(
+        -((lumaNW + lumaNE) - (lumaSW + lumaSE)),
+        (lumaNW + lumaSW) - (lumaNE + lumaSE)
+    );
+
+    float dirReduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float rcpDirMin = 1.0 / (min(abs(dir.x), abs(dir.y)) + dirReduce);
+    dir = clamp(dir * rcpDirMin, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * texel;
+
+    vec3 rgbA = 0.5 * (
+        texture(image, uv + dir * (1.0 / 3.0 - 0.5)).rgb +
+        texture(image, uv + dir * (2.0 / 3.0 - 0.5)).rgb
+    );
+    vec3 rgbB = rgbA * 0.5 + 0.25 * (
+        texture(image, uv - dir * 0.5).rgb +
+        texture(image, uv + dir * 0.5).rgb
+    );
+
+    float lumaB = luma(rgbB);
+    FragColor = vec4((lumaB < lumaMin || lumaB > lumaMax) ? rgbA : rgbB, 1.0);
+}
+"#;
+
+use crate::{Shader, ShaderBuilder};
 
 use std::sync::LazyLock;
 
+/* the built in shaders are permutations of the sources above */
+fn build(builder: ShaderBuilder) -> Shader {
+    builder.build().unwrap_or_else(|err| panic!("{}", err))
+}
+
 pub static DEFAULT_SHADER: LazyLock<Shader> = LazyLock::new(|| {
-    Shader::new_pipeline(DEFAULT_VS, DEFAULT_FS)
+    build(Shader::builder().vertex(DEFAULT_VS).fragment(DEFAULT_FS))
 });
 
+pub static DEFAULT_INSTANCED_SHADER: LazyLock<Shader> = LazyLock::new(|| {
+    build(Shader::builder().vertex(DEFAULT_VS).fragment(DEFAULT_FS).define("HAS_INSTANCING", 1))
+});
 
 pub static RUSSIMP_SHADER: LazyLock<Shader> = LazyLock::new(|| {
-    Shader::new_pipeline(RUSSIMP_VS, DEFAULT_FS)
+    build(Shader::builder().vertex(DEFAULT_VS).fragment(DEFAULT_FS).define("HAS_SKINNING", 1))
+});
+
+pub static SHADOW_SHADER: LazyLock<Shader> = LazyLock::new(|| {
+    build(Shader::builder().vertex(SHADOW_VS).fragment(SHADOW_FS))
+});
+
+pub static SHADOW_INSTANCED_SHADER: LazyLock<Shader> = LazyLock::new(|| {
+    build(Shader::builder().vertex(SHADOW_VS).fragment(SHADOW_FS).define("HAS_INSTANCING", 1))
+});
+
+pub static SHADOW_SKINNED_SHADER: LazyLock<Shader> = LazyLock::new(|| {
+    build(Shader::builder().vertex(SHADOW_VS).fragment(SHADOW_FS).define("HAS_SKINNING", 1))
 });
 
+pub static POST_BRIGHT_SHADER: LazyLock<Shader> = LazyLock::new(|| {
+    build(Shader::builder().vertex(FULLSCREEN_VS).fragment(POST_BRIGHT_FS))
+});
+
+pub static POST_BLUR_SHADER: LazyLock<Shader> = LazyLock::new(|| {
+    build(Shader::builder().vertex(FULLSCREEN_VS).fragment(POST_BLUR_FS))
+});
+
+pub static POST_COMPOSITE_SHADER: LazyLock<Shader> = LazyLock::new(|| {
+    build(Shader::builder().vertex(FULLSCREEN_VS).fragment(POST_COMPOSITE_FS))
+});
+
+pub static POST_FXAA_SHADER: LazyLock<Shader> = LazyLock::new(|| {
+    build(Shader::builder().vertex(FULLSCREEN_VS).fragment(POST_FXAA_FS))
+});
+
+pub static SKYBOX_SHADER: LazyLock<Shader> = LazyLock::new(|| {
+    build(Shader::builder().vertex(SKYBOX_VS).fragment(SKYBOX_FS))
+});
+
+pub static ATMOSPHERE_SHADER: LazyLock<Shader> = LazyLock::new(|| {
+    build(Shader::builder().vertex(SKYBOX_VS).fragment(ATMOSPHERE_FS))
+});
+
+pub static SPRITE_SHADER: LazyLock<Shader> = LazyLock::new(|| {
+    build(Shader::builder().vertex(SPRITE_VS).fragment(SPRITE_FS))
+});
+
+pub static TEXT_SHADER: LazyLock<Shader> = LazyLock::new(|| {
+    build(Shader::builder().vertex(TEXT_VS).fragment(TEXT_FS))
+});
+
+pub static DEBUG_SHADER: LazyLock<Shader> = LazyLock::new(|| {
+    build(Shader::builder().vertex(DEBUG_VS).fragment(DEBUG_FS))
+});
@@ -5,7 +5,7 @@ use glam::{vec3, Mat4, Quat, Vec3};
 use russimp::{bone::{Bone, VertexWeight}, mesh::Mesh, node::Node, scene::Scene};
 
 #[cfg(feature = "skeletal")]
-use crate::{convert_russimp_mat_to_glam_mat, cstr, Shader};
+use crate::{convert_russimp_mat_to_glam_mat, cstr, Shader, MAX_BONES};
 
 #[derive(Copy, Clone)]
 struct KeyPosition {
@@ -309,7 +309,7 @@ pub struct Animator {
 #[cfg(feature = "skeletal")]
 impl Animator {
     pub fn new(animation: Animation) -> Self {
-        let final_bone_matrices = vec![Mat4::IDENTITY; 100];
+        let final_bone_matrices = vec![Mat4::IDENTITY; MAX_BONES];
 
         Self {
             current_time: 0.0,
@@ -355,7 +355,7 @@ impl Animator {
     
         if let Some(bone_info) = self.current_animation.bone_map.get(&node.name) {
             let index = bone_info.id;
-            if index < 100 {
+            if index < MAX_BONES {
                 self.final_bone_matrices[index] =
                     global_transform * convert_russimp_mat_to_glam_mat(bone_info.bone.offset_matrix);
             }
@@ -383,7 +383,7 @@ impl Animator {
     
         if let Some(bone_info) = self.current_animation.bone_map.get(&node.name) {
             let index = bone_info.id;
-            if index < 100 {
+            if index < MAX_BONES {
                 self.final_bone_matrices[index] =
                     global_transform * convert_russimp_mat_to_glam_mat(bone_info.bone.offset_matrix);
             }
@@ -401,13 +401,8 @@ impl Animator {
     }
 
     pub fn upload_uniforms(&self, shader: &Shader) {
-        for (i, matrix) in self.final_bone_matrices.iter().enumerate() {
-            unsafe {
-                shader.uniform_mat4fv(
-                    cstr!(format!("finalBonesMatrices[{}]", i)),
-                    &matrix.to_cols_array(),
-                );
-            }
+        unsafe {
+            shader.uniform_mat4fv_array(cstr!("finalBonesMatrices"), &self.final_bone_matrices);
         }
     }
 }
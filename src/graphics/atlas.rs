@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+
+use glam::{vec2, Vec2};
+use image::{ImageError, RgbaImage};
+
+use crate::{Renderer, TextureDescriptor, TextureHandle, WrapMode};
+
+/// a rectangle of a texture in uv coordinates, `min` being the bottom left corner
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UvRect {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl UvRect {
+    /// the whole texture
+    pub const FULL: Self = Self { min: Vec2::ZERO, max: Vec2::ONE };
+
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        Self { min, max }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    /// maps a uv of the whole texture, 0 to 1, into this rectangle
+    pub fn map(&self, uv: Vec2) -> Vec2 {
+        self.min + uv * self.size()
+    }
+}
+
+impl Default for UvRect {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+/// many images packed into one texture, found by name
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureAtlas {
+    pub texture: TextureHandle,
+    /// size of the texture in pixels
+    pub width: u32,
+    pub height: u32,
+    pub regions: HashMap<String, UvRect>,
+}
+
+impl TextureAtlas {
+    /// slices a texture that already holds a sprite sheet into a grid of equally
+    /// sized cells, named `"{name}_{index}"` row by row starting at the top left
+    pub fn grid(texture: TextureHandle, width: u32, height: u32, name: &str, columns: u32, rows: u32) -> Self {
+        let mut regions = HashMap::new();
+        let cell = vec2(1.0 / columns.max(1) as f32, 1.0 / rows.max(1) as f32);
+
+        for row in 0..rows {
+            for column in 0..columns {
+                // textures are flipped when loaded, so the top row has the highest v
+                let min = vec2(column as f32 * cell.x, 1.0 - (row + 1) as f32 * cell.y);
+                regions.insert(format!("{}_{}", name, row * columns + column), UvRect::new(min, min + cell));
+            }
+        }
+
+        Self { texture, width, height, regions }
+    }
+
+    pub fn get(&self, name: &str) -> Option<UvRect> {
+        self.regions.get(name).copied()
+    }
+
+    /// a cell of a sprite sheet, see `grid` and `AtlasBuilder::add_sprite_sheet`
+    pub fn frame(&self, name: &str, index: u32) -> Option<UvRect> {
+        self.get(&format!("{}_{}", name, index))
+    }
+}
+
+/// collects images and packs them into a `TextureAtlas`
+pub struct AtlasBuilder {
+    images: Vec<(String, RgbaImage)>,
+    /// pixels around each image, filled with its edge so filtering doesn't bleed
+    /// into the neighbours
+    pub padding: u32,
+    /// the atlas grows up to this size before giving up
+    pub max_size: u32,
+    pub descriptor: TextureDescriptor,
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self {
+        Self {
+            images: vec![],
+            padding: 1,
+            max_size: 4096,
+            // mipmaps would mix neighbouring sprites together
+            descriptor: TextureDescriptor {
+                wrap_s: WrapMode::ClampToEdge,
+                wrap_t: WrapMode::ClampToEdge,
+                mipmaps: false,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_image(&mut self, name: &str, image: RgbaImage) -> &mut Self {
+        self.images.push((name.to_string(), image));
+        self
+    }
+
+    pub fn add_file(&mut self, name: &str, path: &str) -> Result<&mut Self, ImageError> {
+        let image = image::open(path)?.to_rgba8();
+        Ok(self.add_image(name, image))
+    }
+
+    /// cuts a sprite sheet into `columns` by `rows` cells, named like in `TextureAtlas::grid`
+    pub fn add_sprite_sheet(&mut self, name: &str, image: &RgbaImage, columns: u32, rows: u32) -> &mut Self {
+        let cell_width = image.width() / columns.max(1);
+        let cell_height = image.height() / rows.max(1);
+
+        for row in 0..rows {
+            for column in 0..columns {
+                let cell = image::imageops::crop_imm(image, column * cell_width, row * cell_height, cell_width, cell_height);
+                self.add_image(&format!("{}_{}", name, row * columns + column), cell.to_image());
+            }
+        }
+
+        self
+    }
+
+    /// packs every image into one texture. None if they don't fit in `max_size`
+    pub fn build(&self, renderer: &mut Renderer) -> Option<TextureAtlas> {
+        let Some((size, placements)) = self.layout() else {
+            println!("Failed to build atlas, the images don't fit in {}x{}", self.max_size, self.max_size);
+            return None;
+        };
+
+        let mut atlas = RgbaImage::new(size, size);
+        let mut regions = HashMap::new();
+
+        for (i, (name, image)) in self.images.iter().enumerate() {
+            let (x, y) = placements[i];
+            blit_padded(&mut atlas, image, x, y, self.padding);
+
+            let (w, h) = (image.width() as f32, image.height() as f32);
+            let (x, y, size) = (x as f32, y as f32, size as f32);
+            let rect = if self.descriptor.flip {
+                UvRect::new(vec2(x, size - y - h) / size, vec2(x + w, size - y) / size)
+            } else {
+                UvRect::new(vec2(x, y) / size, vec2(x + w, y + h) / size)
+            };
+            regions.insert(name.clone(), rect);
+        }
+
+        let texture = renderer.add_texture_from_pixels(size, size, atlas.as_raw(), &self.descriptor).ok()?;
+
+        Some(TextureAtlas { texture, width: size, height: size, regions })
+    }
+
+    /// the smallest square, doubling from 64 up to `max_size`, everything fits in
+    fn layout(&self) -> Option<(u32, Vec<(u32, u32)>)> {
+        let mut size = 64.min(self.max_size);
+        loop {
+            if let Some(placements) = self.pack(size) {
+                return Some((size, placements));
+            }
+            if size >= self.max_size {
+                return None;
+            }
+            size = (size * 2).min(self.max_size);
+        }
+    }
+
+    /// shelf packing, tallest images first. top left corner of each image in the
+    /// order they were added
+    fn pack(&self, size: u32) -> Option<Vec<(u32, u32)>> {
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(self.images[i].1.height()));
+
+        let mut placements = vec![(0, 0); self.images.len()];
+        let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+
+        for i in order {
+            let image = &self.images[i].1;
+            let w = image.width() + self.padding * 2;
+            let h = image.height() + self.padding * 2;
+
+            if x + w > size {
+                x = 0;
+                y += shelf_height;
+                shelf_height = 0;
+            }
+            if x + w > size || y + h > size {
+                return None;
+            }
+
+            placements[i] = (x + self.padding, y + self.padding);
+            x += w;
+            shelf_height = shelf_height.max(h);
+        }
+
+        Some(placements)
+    }
+}
+
+/// copies the image with its edge pixels stretched over the padding
+fn blit_padded(atlas: &mut RgbaImage, image: &RgbaImage, x: u32, y: u32, padding: u32) {
+    let (w, h) = (image.width() as i64, image.height() as i64);
+    if w == 0 || h == 0 {
+        return;
+    }
+    let padding = padding as i64;
+
+    for dy in -padding..h + padding {
+        for dx in -padding..w + padding {
+            let pixel = *image.get_pixel(dx.clamp(0, w - 1) as u32, dy.clamp(0, h - 1) as u32);
+            atlas.put_pixel((x as i64 + dx) as u32, (y as i64 + dy) as u32, pixel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder(sizes: &[(u32, u32)]) -> AtlasBuilder {
+        let mut builder = AtlasBuilder::new();
+        for (i, (w, h)) in sizes.iter().enumerate() {
+            builder.add_image(&i.to_string(), RgbaImage::new(*w, *h));
+        }
+        builder
+    }
+
+    #[test]
+    fn packed_images_stay_inside_and_apart() {
+        let builder = builder(&[(10, 20), (30, 10), (16, 16), (8, 8)]);
+        let placements = builder.pack(64).unwrap();
+
+        let rects: Vec<_> = builder.images.iter().zip(&placements)
+            .map(|((_, image), (x, y))| (*x, *y, x + image.width(), y + image.height()))
+            .collect();
+
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.0 >= builder.padding && a.1 >= builder.padding);
+            assert!(a.2 + builder.padding <= 64 && a.3 + builder.padding <= 64);
+
+            for b in &rects[i + 1..] {
+                let apart = a.2 + builder.padding * 2 <= b.0 || b.2 + builder.padding * 2 <= a.0
+                    || a.3 + builder.padding * 2 <= b.1 || b.3 + builder.padding * 2 <= a.1;
+                assert!(apart, "{:?} and {:?} overlap", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn pack_overflows_when_too_small() {
+        assert!(builder(&[(64, 64)]).pack(64).is_none());
+        assert!(builder(&[(40, 40), (40, 40)]).pack(64).is_none());
+    }
+
+    #[test]
+    fn layout_grows_until_everything_fits() {
+        let (size, _) = builder(&[(100, 100)]).layout().unwrap();
+        assert_eq!(size, 128);
+
+        let (size, _) = builder(&[(20, 20); 4]).layout().unwrap();
+        assert_eq!(size, 64);
+    }
+
+    #[test]
+    fn layout_gives_up_past_max_size() {
+        let mut builder = builder(&[(200, 200)]);
+        builder.max_size = 128;
+
+        assert!(builder.layout().is_none());
+    }
+}
@@ -0,0 +1,292 @@
+use std::f32::consts::PI;
+use std::ffi::CString;
+
+use gl::*;
+use gl::types::GLenum;
+use glam::{vec3, Mat4, Quat, Vec3, Vec4};
+
+use crate::{cstr, CameraBlock, Cubemap, LightHandle, LightKind, Renderer, ATMOSPHERE_SHADER};
+
+/* ray march steps, the sky shader uses more */
+const PRIMARY_STEPS: usize = 8;
+const SECONDARY_STEPS: usize = 4;
+/* faces of the cubemap reflections sample, the sky has no sharp detail */
+const ENVIRONMENT_SIZE: u32 = 64;
+
+/// a procedural sky from rayleigh and mie scattering, drawn instead of the skybox
+/// cubemap. the sun follows `time_of_day` and drags a directional light along
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtmosphereSettings {
+    /// a directional light whose direction and color follow the sun
+    pub sun: Option<LightHandle>,
+    /// hours from 0 to 24, the sun rises at 6 and is highest at 12
+    pub time_of_day: f32,
+    /// how far the sun's path leans away from straight overhead, in radians
+    pub latitude: f32,
+    pub sun_intensity: f32,
+
+    /// scattering coefficients at sea level, per meter
+    pub rayleigh: Vec3,
+    pub mie: f32,
+    /// how much mie scattering goes forward, the glow around the sun
+    pub mie_direction: f32,
+    /// heights at which the air and the haze are 1/e as dense as at sea level, in meters
+    pub rayleigh_height: f32,
+    pub mie_height: f32,
+    /// in meters, the viewer stands on top of the planet
+    pub planet_radius: f32,
+    pub atmosphere_radius: f32,
+
+    /// the renderer's ambient is set to the average sky color times this, None leaves it alone
+    pub ambient_scale: Option<f32>,
+    /// the ambient never gets darker than this, so nights aren't pitch black
+    pub night_ambient: Vec3,
+}
+
+impl Default for AtmosphereSettings {
+    fn default() -> Self {
+        Self {
+            sun: None,
+            time_of_day: 10.0,
+            latitude: 0.4,
+            sun_intensity: 22.0,
+            rayleigh: vec3(5.5e-6, 13.0e-6, 22.4e-6),
+            mie: 21e-6,
+            mie_direction: 0.758,
+            rayleigh_height: 8e3,
+            mie_height: 1.2e3,
+            planet_radius: 6371e3,
+            atmosphere_radius: 6471e3,
+            ambient_scale: Some(0.15),
+            night_ambient: vec3(0.01, 0.01, 0.02),
+        }
+    }
+}
+
+impl AtmosphereSettings {
+    /// points towards the sun. it rises in +x and sets in -x
+    pub fn sun_direction(&self) -> Vec3 {
+        let hour_angle = (self.time_of_day - 12.0) / 24.0 * 2.0 * PI;
+        let overhead = vec3(-hour_angle.sin(), hour_angle.cos(), 0.0);
+
+        Quat::from_rotation_x(self.latitude) * overhead
+    }
+
+    /// how much sunlight makes it through the air to the viewer, black once the sun is down
+    pub fn sun_color(&self) -> Vec3 {
+        let dir = self.sun_direction();
+        let origin = self.origin();
+
+        if ray_sphere(origin, dir, self.planet_radius).is_some_and(|(near, _)| near > 0.0) {
+            return Vec3::ZERO;
+        }
+
+        let Some((_, far)) = ray_sphere(origin, dir, self.atmosphere_radius) else {
+            return Vec3::ONE;
+        };
+        let (rayleigh, mie) = self.optical_depth(origin, dir, far, PRIMARY_STEPS);
+
+        self.extinction(rayleigh, mie)
+    }
+
+    /// single scattering along a view ray, the same model the sky shader uses
+    pub fn sky_color(&self, dir: Vec3) -> Vec3 {
+        let dir = dir.normalize();
+        let sun = self.sun_direction();
+        let origin = self.origin();
+
+        let Some((near, mut far)) = ray_sphere(origin, dir, self.atmosphere_radius) else {
+            return Vec3::ZERO;
+        };
+        if let Some((ground, _)) = ray_sphere(origin, dir, self.planet_radius) {
+            if ground > 0.0 {
+                far = far.min(ground);
+            }
+        }
+
+        let start = near.max(0.0);
+        let step = (far - start) / PRIMARY_STEPS as f32;
+
+        let mut total_rayleigh = Vec3::ZERO;
+        let mut total_mie = Vec3::ZERO;
+        let mut depth_rayleigh = 0.0;
+        let mut depth_mie = 0.0;
+
+        for i in 0..PRIMARY_STEPS {
+            let pos = origin + dir * (start + step * (i as f32 + 0.5));
+            let height = pos.length() - self.planet_radius;
+
+            let step_rayleigh = (-height / self.rayleigh_height).exp() * step;
+            let step_mie = (-height / self.mie_height).exp() * step;
+            depth_rayleigh += step_rayleigh;
+            depth_mie += step_mie;
+
+            // light that reaches this point from the sun, none if the planet is in the way
+            let Some((_, sun_far)) = ray_sphere(pos, sun, self.atmosphere_radius) else { continue; };
+            if ray_sphere(pos, sun, self.planet_radius).is_some_and(|(near, _)| near > 0.0) {
+                continue;
+            }
+            let (sun_rayleigh, sun_mie) = self.optical_depth(pos, sun, sun_far, SECONDARY_STEPS);
+
+            let attenuation = self.extinction(depth_rayleigh + sun_rayleigh, depth_mie + sun_mie);
+            total_rayleigh += step_rayleigh * attenuation;
+            total_mie += step_mie * attenuation;
+        }
+
+        let mu = dir.dot(sun);
+        let g = self.mie_direction;
+        let phase_rayleigh = 3.0 / (16.0 * PI) * (1.0 + mu * mu);
+        let phase_mie = 3.0 / (8.0 * PI) * ((1.0 - g * g) * (1.0 + mu * mu))
+            / ((2.0 + g * g) * (1.0 + g * g - 2.0 * mu * g).powf(1.5));
+
+        self.sun_intensity * (phase_rayleigh * self.rayleigh * total_rayleigh + phase_mie * self.mie * total_mie)
+    }
+
+    /// the sky averaged over the zenith and the horizon all around
+    pub fn ambient_color(&self) -> Vec3 {
+        let directions = [
+            Vec3::Y,
+            vec3(1.0, 0.2, 0.0),
+            vec3(-1.0, 0.2, 0.0),
+            vec3(0.0, 0.2, 1.0),
+            vec3(0.0, 0.2, -1.0),
+        ];
+
+        directions.iter().map(|dir| self.sky_color(*dir)).sum::<Vec3>() / directions.len() as f32
+    }
+
+    /// a meter above the ground
+    fn origin(&self) -> Vec3 {
+        vec3(0.0, self.planet_radius + 1.0, 0.0)
+    }
+
+    fn optical_depth(&self, origin: Vec3, dir: Vec3, length: f32, steps: usize) -> (f32, f32) {
+        let step = length / steps as f32;
+        let mut rayleigh = 0.0;
+        let mut mie = 0.0;
+
+        for i in 0..steps {
+            let height = (origin + dir * step * (i as f32 + 0.5)).length() - self.planet_radius;
+            rayleigh += (-height / self.rayleigh_height).exp() * step;
+            mie += (-height / self.mie_height).exp() * step;
+        }
+
+        (rayleigh, mie)
+    }
+
+    fn extinction(&self, rayleigh: f32, mie: f32) -> Vec3 {
+        (-(self.rayleigh * rayleigh + Vec3::splat(self.mie * mie))).exp()
+    }
+}
+
+/// distances along the ray to where it enters and leaves the sphere around the origin
+fn ray_sphere(origin: Vec3, dir: Vec3, radius: f32) -> Option<(f32, f32)> {
+    let b = origin.dot(dir);
+    let c = origin.length_squared() - radius * radius;
+    let d = b * b - c;
+    if d < 0.0 {
+        return None;
+    }
+
+    let d = d.sqrt();
+    Some((-b - d, -b + d))
+}
+
+impl Renderer {
+    /// points the sun light along the atmosphere's sun, updates the ambient and
+    /// renders the sky again for reflections if the settings changed
+    pub(crate) fn update_atmosphere(&mut self) {
+        let Some(atmosphere) = self.atmosphere else {
+            self.atmosphere_environment = None;
+            return;
+        };
+
+        if let Some(light) = atmosphere.sun.and_then(|handle| self.lights.get_mut(handle)) {
+            light.kind = LightKind::Directional { direction: -atmosphere.sun_direction() };
+            light.color = atmosphere.sun_color();
+        }
+
+        if let Some(scale) = atmosphere.ambient_scale {
+            self.ambient = (atmosphere.ambient_color() * scale).max(atmosphere.night_ambient);
+        }
+
+        if self.atmosphere_environment.as_ref().map(|(_, rendered)| rendered) != Some(&atmosphere) {
+            unsafe {
+                self.render_atmosphere_environment(&atmosphere);
+            }
+        }
+    }
+
+    /// draws the sky into each face of a cubemap from the origin. it overwrites the camera
+    /// block, which `draw` fills again before anything else uses it
+    unsafe fn render_atmosphere_environment(&mut self, atmosphere: &AtmosphereSettings) {
+        let cubemap = match self.atmosphere_environment.take() {
+            Some((cubemap, _)) => cubemap,
+            None => Cubemap::empty(ENVIRONMENT_SIZE),
+        };
+
+        let mut previous_fbo = 0;
+        GetIntegerv(FRAMEBUFFER_BINDING, &mut previous_fbo);
+        let mut viewport = [0; 4];
+        GetIntegerv(VIEWPORT, viewport.as_mut_ptr());
+        let depth_test = IsEnabled(DEPTH_TEST) == TRUE;
+        let cull_face = IsEnabled(CULL_FACE) == TRUE;
+
+        let mut fbo = 0;
+        GenFramebuffers(1, &mut fbo);
+        BindFramebuffer(FRAMEBUFFER, fbo);
+        Viewport(0, 0, cubemap.size as i32, cubemap.size as i32);
+        Disable(DEPTH_TEST);
+        Disable(CULL_FACE);
+
+        self.use_atmosphere_shader(atmosphere);
+        BindVertexArray(self.empty_vao);
+
+        // same faces as the point light shadow maps
+        let proj = Mat4::perspective_rh_gl(90f32.to_radians(), 1.0, 0.1, 10.0);
+        let faces = [
+            (Vec3::X, Vec3::NEG_Y),
+            (Vec3::NEG_X, Vec3::NEG_Y),
+            (Vec3::Y, Vec3::Z),
+            (Vec3::NEG_Y, Vec3::NEG_Z),
+            (Vec3::Z, Vec3::NEG_Y),
+            (Vec3::NEG_Z, Vec3::NEG_Y),
+        ];
+        for (face, (direction, up)) in faces.into_iter().enumerate() {
+            FramebufferTexture2D(FRAMEBUFFER, COLOR_ATTACHMENT0, TEXTURE_CUBE_MAP_POSITIVE_X + face as GLenum, cubemap.id, 0);
+            self.camera_ubo.upload(&CameraBlock {
+                view: Mat4::look_at_rh(Vec3::ZERO, direction, up),
+                proj,
+                view_pos: Vec4::W,
+                debug_view: [0; 4],
+            });
+            DrawArrays(TRIANGLE_STRIP, 0, 14);
+        }
+
+        BindVertexArray(0);
+        UseProgram(0);
+        BindFramebuffer(FRAMEBUFFER, previous_fbo as u32);
+        DeleteFramebuffers(1, &fbo);
+        Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        if depth_test { Enable(DEPTH_TEST); }
+        if cull_face { Enable(CULL_FACE); }
+
+        self.atmosphere_environment = Some((cubemap, *atmosphere));
+    }
+
+    /// binds the sky shader with the current settings, the caller draws the cube
+    pub(crate) unsafe fn use_atmosphere_shader(&self, atmosphere: &AtmosphereSettings) {
+        let shader = *ATMOSPHERE_SHADER;
+        shader.use_shader();
+
+        shader.uniform_vec3f(cstr!("sunDirection"), &atmosphere.sun_direction());
+        shader.uniform_1f(cstr!("sunIntensity"), atmosphere.sun_intensity);
+        shader.uniform_vec3f(cstr!("rayleighCoefficient"), &atmosphere.rayleigh);
+        shader.uniform_1f(cstr!("mieCoefficient"), atmosphere.mie);
+        shader.uniform_1f(cstr!("mieDirection"), atmosphere.mie_direction);
+        shader.uniform_1f(cstr!("rayleighHeight"), atmosphere.rayleigh_height);
+        shader.uniform_1f(cstr!("mieHeight"), atmosphere.mie_height);
+        shader.uniform_1f(cstr!("planetRadius"), atmosphere.planet_radius);
+        shader.uniform_1f(cstr!("atmosphereRadius"), atmosphere.atmosphere_radius);
+    }
+}
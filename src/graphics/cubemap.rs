@@ -0,0 +1,244 @@
+use std::f32::consts::PI;
+
+use gl::*;
+use gl::types::{GLenum, GLint, GLuint};
+use glam::{vec3, Vec3};
+use image::Rgb32FImage;
+
+use crate::{Handle, Renderer, SKYBOX_SHADER};
+
+/// the skybox, or the atmosphere rendered into a cubemap, is bound here every frame,
+/// so any material with a reflectivity reflects it without having to set an environment of its own
+pub const ENVIRONMENT_TEXTURE_UNIT: u32 = 7;
+
+/// six square textures making up the inside of a cube, sampled by direction
+#[derive(Debug, PartialEq)]
+pub struct Cubemap {
+    pub id: GLuint,
+    /// width and height of each face
+    pub size: u32,
+}
+
+pub type CubemapHandle = Handle<Cubemap>;
+
+impl Cubemap {
+    /// loads the faces in opengl's order: +x, -x, +y, -y, +z, -z
+    /// (right, left, top, bottom, front, back)
+    pub fn from_faces(paths: [&str; 6]) -> Option<Self> {
+        let mut faces = vec![];
+        for path in paths {
+            match image::open(path) {
+                Ok(img) => faces.push(img.to_rgba8()),
+                Err(err) => {
+                    println!("Failed to load cubemap face {}: {}", path, err);
+                    return None;
+                }
+            }
+        }
+
+        let size = faces[0].width();
+        if faces.iter().any(|face| face.width() != size || face.height() != size) {
+            println!("Failed to load cubemap, every face has to be square and the same size");
+            return None;
+        }
+
+        let id = unsafe { create_cubemap() };
+        unsafe {
+            // cubemap faces start at the top left, unlike 2D textures they aren't flipped
+            for (i, face) in faces.iter().enumerate() {
+                upload_face(i as u32, size, RGBA8, RGBA, UNSIGNED_BYTE, face.as_ptr() as *const std::ffi::c_void);
+            }
+            GenerateMipmap(TEXTURE_CUBE_MAP);
+            BindTexture(TEXTURE_CUBE_MAP, 0);
+        }
+
+        Some(Self { id, size })
+    }
+
+    /// projects an equirectangular (latitude-longitude) image, usually an .hdr,
+    /// onto six faces of `size` pixels. colors keep their full range
+    pub fn from_equirectangular(path: &str, size: u32) -> Option<Self> {
+        let img = match image::open(path) {
+            Ok(img) => img.to_rgb32f(),
+            Err(err) => {
+                println!("Failed to load equirectangular image {}: {}", path, err);
+                return None;
+            }
+        };
+
+        let id = unsafe { create_cubemap() };
+        for face in 0..6 {
+            let mut pixels = Vec::with_capacity((size * size * 3) as usize);
+
+            for y in 0..size {
+                for x in 0..size {
+                    let s = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                    let t = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                    let color = sample_equirectangular(&img, face_direction(face, s, t));
+                    pixels.extend_from_slice(&color.to_array());
+                }
+            }
+
+            unsafe {
+                upload_face(face, size, RGB16F, RGB, FLOAT, pixels.as_ptr() as *const std::ffi::c_void);
+            }
+        }
+
+        unsafe {
+            GenerateMipmap(TEXTURE_CUBE_MAP);
+            BindTexture(TEXTURE_CUBE_MAP, 0);
+        }
+
+        Some(Self { id, size })
+    }
+
+    /// faces with nothing in them yet, to be rendered into. colors keep their full range
+    pub fn empty(size: u32) -> Self {
+        let id = unsafe { create_cubemap() };
+        unsafe {
+            // there are no mips to sample until something renders into it
+            TexParameteri(TEXTURE_CUBE_MAP, TEXTURE_MIN_FILTER, LINEAR as GLint);
+            for face in 0..6 {
+                upload_face(face, size, RGB16F, RGB, FLOAT, std::ptr::null());
+            }
+            BindTexture(TEXTURE_CUBE_MAP, 0);
+        }
+
+        Self { id, size }
+    }
+}
+
+impl Drop for Cubemap {
+    fn drop(&mut self) {
+        unsafe {
+            DeleteTextures(1, &self.id);
+        }
+    }
+}
+
+unsafe fn create_cubemap() -> GLuint {
+    let mut id = 0;
+    GenTextures(1, &mut id);
+    BindTexture(TEXTURE_CUBE_MAP, id);
+    TexParameteri(TEXTURE_CUBE_MAP, TEXTURE_MIN_FILTER, LINEAR_MIPMAP_LINEAR as GLint);
+    TexParameteri(TEXTURE_CUBE_MAP, TEXTURE_MAG_FILTER, LINEAR as GLint);
+    TexParameteri(TEXTURE_CUBE_MAP, TEXTURE_WRAP_S, CLAMP_TO_EDGE as GLint);
+    TexParameteri(TEXTURE_CUBE_MAP, TEXTURE_WRAP_T, CLAMP_TO_EDGE as GLint);
+    TexParameteri(TEXTURE_CUBE_MAP, TEXTURE_WRAP_R, CLAMP_TO_EDGE as GLint);
+
+    id
+}
+
+unsafe fn upload_face(face: u32, size: u32, internal: GLenum, format: GLenum, ty: GLenum, data: *const std::ffi::c_void) {
+    TexImage2D(
+        TEXTURE_CUBE_MAP_POSITIVE_X + face,
+        0,
+        internal as GLint,
+        size as i32,
+        size as i32,
+        0,
+        format,
+        ty,
+        data,
+    );
+}
+
+/// the direction a texel of a face points to, `s` and `t` going from -1 to 1
+/// across the face. follows the face layout in the opengl spec
+fn face_direction(face: u32, s: f32, t: f32) -> Vec3 {
+    let dir = match face {
+        0 => vec3(1.0, -t, -s),
+        1 => vec3(-1.0, -t, s),
+        2 => vec3(s, 1.0, t),
+        3 => vec3(s, -1.0, -t),
+        4 => vec3(s, -t, 1.0),
+        _ => vec3(-s, -t, -1.0),
+    };
+
+    dir.normalize()
+}
+
+fn sample_equirectangular(img: &Rgb32FImage, dir: Vec3) -> Vec3 {
+    let u = dir.z.atan2(dir.x) / (2.0 * PI) + 0.5;
+    let v = 0.5 - dir.y.clamp(-1.0, 1.0).asin() / PI;
+
+    // bilinear, wrapping around horizontally
+    let (w, h) = (img.width() as f32, img.height() as f32);
+    let x = u * w - 0.5;
+    let y = (v * h - 0.5).clamp(0.0, h - 1.0);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+
+    let texel = |x: f32, y: f32| {
+        let x = x.rem_euclid(w) as u32;
+        let y = y.clamp(0.0, h - 1.0) as u32;
+        Vec3::from_array(img.get_pixel(x, y).0)
+    };
+
+    let top = texel(x0, y0).lerp(texel(x0 + 1.0, y0), fx);
+    let bottom = texel(x0, y0 + 1.0).lerp(texel(x0 + 1.0, y0 + 1.0), fx);
+    top.lerp(bottom, fy)
+}
+
+impl Renderer {
+    pub fn add_cubemap(&mut self, cubemap: Cubemap) -> Option<CubemapHandle> {
+        Some(self.cubemaps.insert(cubemap))
+    }
+
+    pub fn destroy_cubemap(&mut self, handle: CubemapHandle) {
+        if self.cubemaps.remove(handle).is_some() {
+            if self.skybox == Some(handle) {
+                self.skybox = None;
+            }
+        } else {
+            println!("Failed to remove cubemap");
+        }
+    }
+
+    /// binds the sky where materials look for their environment, the atmosphere if there is one
+    pub(crate) unsafe fn bind_environment(&self) {
+        let id = match (&self.atmosphere, &self.atmosphere_environment) {
+            (Some(_), Some((cubemap, _))) => cubemap.id,
+            _ => self.skybox.and_then(|handle| self.cubemaps.get(handle)).map_or(0, |cubemap| cubemap.id),
+        };
+
+        ActiveTexture(TEXTURE0 + ENVIRONMENT_TEXTURE_UNIT);
+        BindTexture(TEXTURE_CUBE_MAP, id);
+        ActiveTexture(TEXTURE0);
+    }
+
+    /// draws the skybox or the atmosphere behind everything already drawn. the cube is pushed to the
+    /// far plane in the shader so it only fills what is still empty
+    pub(crate) unsafe fn draw_skybox(&self) {
+        let cubemap = self.skybox.and_then(|handle| self.cubemaps.get(handle));
+        if cubemap.is_none() && self.atmosphere.is_none() {
+            return;
+        }
+
+        let cull_face = IsEnabled(CULL_FACE) == TRUE;
+        let mut depth_func = 0;
+        GetIntegerv(DEPTH_FUNC, &mut depth_func);
+
+        Disable(CULL_FACE);
+        DepthFunc(LEQUAL);
+        DepthMask(FALSE);
+
+        // the atmosphere takes the place of the cubemap when both are set
+        if let Some(atmosphere) = &self.atmosphere {
+            self.use_atmosphere_shader(atmosphere);
+        } else if let Some(cubemap) = cubemap {
+            SKYBOX_SHADER.use_shader();
+            ActiveTexture(TEXTURE0);
+            BindTexture(TEXTURE_CUBE_MAP, cubemap.id);
+        }
+        BindVertexArray(self.empty_vao);
+        DrawArrays(TRIANGLE_STRIP, 0, 14);
+
+        BindVertexArray(0);
+        BindTexture(TEXTURE_CUBE_MAP, 0);
+        UseProgram(0);
+        DepthMask(TRUE);
+        DepthFunc(depth_func as GLenum);
+        if cull_face { Enable(CULL_FACE); }
+    }
+}
@@ -0,0 +1,239 @@
+use std::cell::Cell;
+use std::f32::consts::TAU;
+use std::mem::size_of;
+
+use gl::{*, types::{GLsizei, GLuint}};
+use glam::{vec3, Mat4, Quat, Vec3, Vec4};
+
+use crate::{Camera, DEBUG_SHADER};
+
+/* segments used for circles and spheres */
+const CIRCLE_SEGMENTS: usize = 24;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DebugVertex {
+    position: Vec3,
+    color: Vec4,
+}
+
+/// immediate mode lines for visualizing things like bounding volumes and bones.
+/// everything added is drawn once with the next `Renderer::draw` and cleared by
+/// `Renderer::update`, so add shapes every frame between the two.
+///
+/// without the `debug-draw` feature every call does nothing and nothing is drawn
+pub struct DebugDraw {
+    /// shapes added while this is set are drawn over everything instead of being depth tested
+    pub on_top: bool,
+    /// hides everything without having to remove the calls
+    pub enabled: bool,
+
+    depth_tested: Vec<DebugVertex>,
+    overlay: Vec<DebugVertex>,
+
+    vao: GLuint,
+    vbo: GLuint,
+    /// vertices the buffer can hold before it has to grow
+    capacity: Cell<usize>,
+}
+
+impl DebugDraw {
+    pub(crate) fn new() -> Self {
+        #[cfg_attr(not(feature = "debug-draw"), allow(unused_mut))]
+        let mut debug = Self {
+            on_top: false,
+            enabled: true,
+            depth_tested: vec![],
+            overlay: vec![],
+            vao: 0,
+            vbo: 0,
+            capacity: Cell::new(0),
+        };
+
+        #[cfg(feature = "debug-draw")]
+        unsafe {
+            GenVertexArrays(1, &mut debug.vao);
+            GenBuffers(1, &mut debug.vbo);
+
+            BindVertexArray(debug.vao);
+            BindBuffer(ARRAY_BUFFER, debug.vbo);
+            crate::gen_attrib_pointers!(DebugVertex, 0 => position: 3, 1 => color: 4);
+            BindVertexArray(0);
+        }
+
+        debug
+    }
+
+    pub fn line(&mut self, from: Vec3, to: Vec3, color: Vec4) {
+        #[cfg(feature = "debug-draw")]
+        {
+            let lines = if self.on_top { &mut self.overlay } else { &mut self.depth_tested };
+            lines.push(DebugVertex { position: from, color });
+            lines.push(DebugVertex { position: to, color });
+        }
+        #[cfg(not(feature = "debug-draw"))]
+        let _ = (from, to, color);
+    }
+
+    /// a line with a head at `to`
+    pub fn arrow(&mut self, from: Vec3, to: Vec3, color: Vec4) {
+        self.line(from, to, color);
+
+        let dir = to - from;
+        let length = dir.length();
+        if length <= f32::EPSILON {
+            return;
+        }
+
+        let dir = dir / length;
+        let head = length * 0.2;
+        let (side, up) = dir.any_orthonormal_pair();
+        for offset in [side, -side, up, -up] {
+            self.line(to, to - dir * head + offset * head * 0.5, color);
+        }
+    }
+
+    /// a small cross
+    pub fn point(&mut self, position: Vec3, size: f32, color: Vec4) {
+        let half = size / 2.0;
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            self.line(position - axis * half, position + axis * half, color);
+        }
+    }
+
+    pub fn wire_box(&mut self, center: Vec3, half_extents: Vec3, rotation: Quat, color: Vec4) {
+        let corner = |x: f32, y: f32, z: f32| center + rotation * (half_extents * vec3(x, y, z));
+
+        for (a, b) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+            // along x, y and z
+            self.line(corner(-1.0, a, b), corner(1.0, a, b), color);
+            self.line(corner(a, -1.0, b), corner(a, 1.0, b), color);
+            self.line(corner(a, b, -1.0), corner(a, b, 1.0), color);
+        }
+    }
+
+    /// an axis aligned box from its corners
+    pub fn aabb(&mut self, min: Vec3, max: Vec3, color: Vec4) {
+        self.wire_box((min + max) / 2.0, (max - min) / 2.0, Quat::IDENTITY, color);
+    }
+
+    pub fn circle(&mut self, center: Vec3, normal: Vec3, radius: f32, color: Vec4) {
+        let (u, v) = normal.normalize_or_zero().any_orthonormal_pair();
+        let point = |i: usize| {
+            let angle = i as f32 / CIRCLE_SEGMENTS as f32 * TAU;
+            center + (u * angle.cos() + v * angle.sin()) * radius
+        };
+
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), color);
+        }
+    }
+
+    /// three circles, one around each axis
+    pub fn wire_sphere(&mut self, center: Vec3, radius: f32, color: Vec4) {
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            self.circle(center, axis, radius, color);
+        }
+    }
+
+    /// red, green and blue lines along the x, y and z axes of the transform
+    pub fn axes(&mut self, transform: Mat4, size: f32) {
+        let origin = transform.transform_point3(Vec3::ZERO);
+        let colors = [Vec4::new(1.0, 0.0, 0.0, 1.0), Vec4::new(0.0, 1.0, 0.0, 1.0), Vec4::new(0.0, 0.0, 1.0, 1.0)];
+
+        for (axis, color) in [Vec3::X, Vec3::Y, Vec3::Z].into_iter().zip(colors) {
+            self.line(origin, transform.transform_point3(axis * size), color);
+        }
+    }
+
+    /// `cells` by `cells` squares on the xz plane, centered on `center`
+    pub fn grid(&mut self, center: Vec3, cell_size: f32, cells: u32, color: Vec4) {
+        let half = cells as f32 * cell_size / 2.0;
+
+        for i in 0..=cells {
+            let offset = i as f32 * cell_size - half;
+            self.line(center + vec3(offset, 0.0, -half), center + vec3(offset, 0.0, half), color);
+            self.line(center + vec3(-half, 0.0, offset), center + vec3(half, 0.0, offset), color);
+        }
+    }
+
+    /// the volume a projection * view matrix can see
+    pub fn frustum(&mut self, view_proj: Mat4, color: Vec4) {
+        let inverse = view_proj.inverse();
+        let corner = |x: f32, y: f32, z: f32| inverse.project_point3(vec3(x, y, z));
+
+        let near = [corner(-1.0, -1.0, -1.0), corner(1.0, -1.0, -1.0), corner(1.0, 1.0, -1.0), corner(-1.0, 1.0, -1.0)];
+        let far = [corner(-1.0, -1.0, 1.0), corner(1.0, -1.0, 1.0), corner(1.0, 1.0, 1.0), corner(-1.0, 1.0, 1.0)];
+
+        for i in 0..4 {
+            self.line(near[i], near[(i + 1) % 4], color);
+            self.line(far[i], far[(i + 1) % 4], color);
+            self.line(near[i], far[i], color);
+        }
+    }
+
+    pub fn camera_frustum(&mut self, camera: &Camera, color: Vec4) {
+        self.frustum(camera.proj * camera.view, color);
+    }
+
+    /// forgets everything added so far
+    pub fn clear(&mut self) {
+        self.depth_tested.clear();
+        self.overlay.clear();
+    }
+
+    /// both lists go in the same buffer, the depth tested lines first
+    pub(crate) unsafe fn draw(&self) {
+        if !cfg!(feature = "debug-draw") || !self.enabled {
+            return;
+        }
+
+        let count = self.depth_tested.len() + self.overlay.len();
+        if count == 0 {
+            return;
+        }
+
+        BindVertexArray(self.vao);
+        BindBuffer(ARRAY_BUFFER, self.vbo);
+        if count > self.capacity.get() {
+            self.capacity.set(count.next_power_of_two());
+            BufferData(ARRAY_BUFFER, (self.capacity.get() * size_of::<DebugVertex>()) as isize, std::ptr::null(), STREAM_DRAW);
+        }
+
+        let stride = size_of::<DebugVertex>();
+        BufferSubData(ARRAY_BUFFER, 0, (self.depth_tested.len() * stride) as isize, self.depth_tested.as_ptr() as *const std::ffi::c_void);
+        BufferSubData(
+            ARRAY_BUFFER,
+            (self.depth_tested.len() * stride) as isize,
+            (self.overlay.len() * stride) as isize,
+            self.overlay.as_ptr() as *const std::ffi::c_void,
+        );
+
+        DEBUG_SHADER.use_shader();
+
+        if !self.depth_tested.is_empty() {
+            DrawArrays(LINES, 0, self.depth_tested.len() as GLsizei);
+        }
+
+        if !self.overlay.is_empty() {
+            let depth_test = IsEnabled(DEPTH_TEST) == TRUE;
+            Disable(DEPTH_TEST);
+            DrawArrays(LINES, self.depth_tested.len() as i32, self.overlay.len() as GLsizei);
+            if depth_test { Enable(DEPTH_TEST); }
+        }
+
+        BindVertexArray(0);
+        UseProgram(0);
+    }
+}
+
+impl Drop for DebugDraw {
+    fn drop(&mut self) {
+        if self.vao != 0 {
+            unsafe {
+                DeleteVertexArrays(1, &self.vao);
+                DeleteBuffers(1, &self.vbo);
+            }
+        }
+    }
+}
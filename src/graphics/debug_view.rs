@@ -0,0 +1,108 @@
+use gl::*;
+use gl::types::GLenum;
+
+use crate::Renderer;
+
+/// what the default shaders output instead of the lit scene, for diagnosing
+/// meshes. custom shaders can read it from `debugView.x` in the camera block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DebugView {
+    #[default]
+    Lit,
+    /// the lit scene with every triangle outlined on top
+    Wireframe,
+    /// the interpolated vertex normal as a color, x y z mapped to r g b
+    Normals,
+    /// a checkerboard over the uvs, shows stretching and seams
+    UvChecker,
+    /// color and textures without any lighting or shadows
+    FlatLighting,
+    /// white up close, fading to black at the far plane
+    Depth,
+    /// every layer of surfaces adds to the heat, brighter means more overdraw
+    Overdraw,
+}
+
+/* what DEFAULT_FS outputs while the outlines are drawn over the lit scene */
+const WIREFRAME_LINES: i32 = 7;
+
+impl DebugView {
+    pub const ALL: [DebugView; 7] = [
+        DebugView::Lit,
+        DebugView::Wireframe,
+        DebugView::Normals,
+        DebugView::UvChecker,
+        DebugView::FlatLighting,
+        DebugView::Depth,
+        DebugView::Overdraw,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DebugView::Lit => "lit",
+            DebugView::Wireframe => "wireframe",
+            DebugView::Normals => "normals",
+            DebugView::UvChecker => "uv checker",
+            DebugView::FlatLighting => "flat lighting",
+            DebugView::Depth => "depth",
+            DebugView::Overdraw => "overdraw",
+        }
+    }
+
+    /* matches the constants in DEFAULT_FS */
+    pub(crate) fn id(&self) -> i32 {
+        match self {
+            DebugView::Lit | DebugView::Wireframe => 0,
+            DebugView::Normals => 2,
+            DebugView::UvChecker => 3,
+            DebugView::FlatLighting => 4,
+            DebugView::Depth => 5,
+            DebugView::Overdraw => 6,
+        }
+    }
+
+    /// the skybox would only get in the way of the other views
+    pub(crate) fn shows_sky(&self) -> bool {
+        matches!(self, DebugView::Lit | DebugView::Wireframe)
+    }
+}
+
+impl Renderer {
+    /// draws every mesh, model and skeletal mesh the way the debug view asks for,
+    /// along with the skybox when the view shows it
+    pub(crate) unsafe fn draw_meshes_with_debug_view(&self) {
+        match self.debug_view {
+            DebugView::Overdraw => {
+                let depth_test = IsEnabled(DEPTH_TEST) == TRUE;
+                Disable(DEPTH_TEST);
+                Enable(BLEND);
+                BlendFunc(ONE, ONE);
+
+                self.draw_meshes(false);
+
+                BlendFunc(SRC_ALPHA, ONE_MINUS_SRC_ALPHA);
+                if depth_test { Enable(DEPTH_TEST); }
+            }
+            DebugView::Wireframe => {
+                self.draw_meshes(true);
+
+                // the same meshes again as lines, pulled slightly towards the camera
+                let mut depth_func = 0;
+                GetIntegerv(DEPTH_FUNC, &mut depth_func);
+                self.upload_camera_block(WIREFRAME_LINES);
+                PolygonMode(FRONT_AND_BACK, LINE);
+                Enable(POLYGON_OFFSET_LINE);
+                PolygonOffset(-1.0, -1.0);
+                DepthFunc(LEQUAL);
+
+                self.draw_meshes(false);
+
+                DepthFunc(depth_func as GLenum);
+                Disable(POLYGON_OFFSET_LINE);
+                PolygonMode(FRONT_AND_BACK, FILL);
+                self.upload_camera_block(self.debug_view.id());
+            }
+            _ => self.draw_meshes(self.debug_view.shows_sky()),
+        }
+    }
+}
@@ -0,0 +1,555 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::mem::size_of;
+
+use gl::{*, types::{GLsizei, GLuint}};
+use glam::{vec2, Mat3, Mat4, Quat, Vec2, Vec3, Vec4};
+
+use crate::{create_texture_from_pixels, cstr, gen_attrib_pointers, update_texture, BlendState, Handle, Renderer, TextureDescriptor, UvRect, WrapMode, TEXT_SHADER};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FontSettings {
+    /// glyphs are rasterized at this many pixels per em and scaled from there
+    pub size: f32,
+    /// stores distances to the outline instead of coverage, so text stays crisp
+    /// at any scale. slower to rasterize
+    pub sdf: bool,
+    /// how far from the outline distances are stored, in pixels
+    pub sdf_spread: u32,
+    /// width and height of the glyph atlas
+    pub atlas_size: u32,
+}
+
+impl Default for FontSettings {
+    fn default() -> Self {
+        Self {
+            size: 48.0,
+            sdf: true,
+            sdf_spread: 6,
+            atlas_size: 1024,
+        }
+    }
+}
+
+/// where a character is in the atlas and how it sits on the baseline, in pixels
+/// at the size the font was rasterized at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Glyph {
+    pub uv: UvRect,
+    /// bottom left corner of the bitmap relative to the pen, y up
+    pub offset: Vec2,
+    pub size: Vec2,
+    pub advance: f32,
+}
+
+/// a ttf or otf font whose glyphs are rasterized into an atlas the first time
+/// they are used
+pub struct Font {
+    font: fontdue::Font,
+    pub settings: FontSettings,
+    pub texture: GLuint,
+    glyphs: HashMap<char, Glyph>,
+    /// next free spot in the atlas and the height of the current row
+    cursor: (u32, u32, u32),
+
+    /* line metrics at `settings.size` */
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_gap: f32,
+}
+
+pub type FontHandle = Handle<Font>;
+
+impl Font {
+    pub fn from_file(path: &str, settings: FontSettings) -> Option<Self> {
+        match std::fs::read(path) {
+            Ok(bytes) => Self::from_bytes(&bytes, settings),
+            Err(err) => {
+                println!("Failed to load font {}: {}", path, err);
+                None
+            }
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8], settings: FontSettings) -> Option<Self> {
+        let font = match fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default()) {
+            Ok(font) => font,
+            Err(err) => {
+                println!("Failed to parse font: {}", err);
+                return None;
+            }
+        };
+
+        let (ascent, descent, line_gap) = font.horizontal_line_metrics(settings.size)
+            .map_or((settings.size, 0.0, 0.0), |line| (line.ascent, line.descent, line.line_gap));
+
+        let descriptor = TextureDescriptor {
+            wrap_s: WrapMode::ClampToEdge,
+            wrap_t: WrapMode::ClampToEdge,
+            mipmaps: false,
+            flip: false,
+            ..Default::default()
+        };
+        let size = settings.atlas_size;
+        let pixels = vec![0; (size * size * 4) as usize];
+        let texture = unsafe { create_texture_from_pixels(size, size, &pixels, &descriptor).ok()? };
+
+        let mut font = Self {
+            font,
+            settings,
+            texture,
+            glyphs: HashMap::new(),
+            cursor: (0, 0, 0),
+            ascent,
+            descent,
+            line_gap,
+        };
+        font.cache_glyphs(&(' '..='~').collect::<String>());
+
+        Some(font)
+    }
+
+    /// rasterizes every character of `text` that isn't in the atlas yet
+    pub fn cache_glyphs(&mut self, text: &str) {
+        for character in text.chars() {
+            if !self.glyphs.contains_key(&character) && !character.is_control() {
+                let glyph = self.rasterize(character);
+                self.glyphs.insert(character, glyph);
+            }
+        }
+    }
+
+    pub fn glyph(&self, character: char) -> Option<&Glyph> {
+        self.glyphs.get(&character)
+    }
+
+    fn rasterize(&mut self, character: char) -> Glyph {
+        let (metrics, coverage) = self.font.rasterize(character, self.settings.size);
+        let padding = if self.settings.sdf { self.settings.sdf_spread } else { 1 };
+
+        let mut glyph = Glyph {
+            uv: UvRect::new(Vec2::ZERO, Vec2::ZERO),
+            offset: vec2(metrics.xmin as f32 - padding as f32, metrics.ymin as f32 - padding as f32),
+            size: Vec2::ZERO,
+            advance: metrics.advance_width,
+        };
+        if metrics.width == 0 || metrics.height == 0 {
+            return glyph;
+        }
+
+        let width = metrics.width as u32 + padding * 2;
+        let height = metrics.height as u32 + padding * 2;
+
+        // coverage with room around it, rows going from the bottom up like the atlas
+        let mut alpha = vec![0u8; (width * height) as usize];
+        for y in 0..metrics.height {
+            for x in 0..metrics.width {
+                let row = height as usize - 1 - (y + padding as usize);
+                alpha[row * width as usize + x + padding as usize] = coverage[y * metrics.width + x];
+            }
+        }
+        if self.settings.sdf {
+            alpha = signed_distance_field(&alpha, width, height, self.settings.sdf_spread);
+        }
+
+        let atlas_size = self.settings.atlas_size;
+        let (mut x, mut y, mut row_height) = self.cursor;
+        if x + width > atlas_size {
+            x = 0;
+            y += row_height;
+            row_height = 0;
+        }
+        if x + width > atlas_size || y + height > atlas_size {
+            println!("Failed to cache glyph {:?}, the font atlas is full", character);
+            return glyph;
+        }
+        self.cursor = (x + width, y, row_height.max(height));
+
+        let pixels: Vec<u8> = alpha.iter().flat_map(|&a| [255, 255, 255, a]).collect();
+        if let Err(err) = unsafe { update_texture(self.texture, x, y, width, height, &pixels) } {
+            println!("Failed to upload glyph {:?}: {}", character, err);
+            return glyph;
+        }
+
+        let atlas_size = atlas_size as f32;
+        glyph.uv = UvRect::new(
+            vec2(x as f32, y as f32) / atlas_size,
+            vec2((x + width) as f32, (y + height) as f32) / atlas_size,
+        );
+        glyph.size = vec2(width as f32, height as f32);
+
+        glyph
+    }
+
+    /// places every glyph of `text`. coordinates are in units of `style.size`, y
+    /// going down from the top of the first line, and x lined up according to
+    /// `style.align` around 0
+    pub fn layout(&self, text: &str, style: &TextStyle) -> Vec<GlyphQuad> {
+        let scale = style.size / self.settings.size;
+        let line_height = (self.ascent - self.descent + self.line_gap) * scale * style.line_spacing;
+
+        let lines = self.wrap(text, style);
+        let widths: Vec<f32> = lines.iter().map(|line| self.line_width(line) * scale).collect();
+
+        let mut quads = vec![];
+        for (i, line) in lines.iter().enumerate() {
+            let start = match style.align {
+                TextAlign::Left => 0.0,
+                TextAlign::Center => -widths[i] / 2.0,
+                TextAlign::Right => -widths[i],
+            };
+            let baseline = self.ascent * scale + i as f32 * line_height;
+
+            let mut pen = 0.0;
+            let mut previous = None;
+            for character in line.chars() {
+                let Some(glyph) = self.glyphs.get(&character) else { continue; };
+                if let Some(previous) = previous {
+                    pen += self.font.horizontal_kern(previous, character, self.settings.size).unwrap_or(0.0);
+                }
+
+                if glyph.size != Vec2::ZERO {
+                    let min = vec2(start + (pen + glyph.offset.x) * scale, baseline - (glyph.offset.y + glyph.size.y) * scale);
+                    quads.push(GlyphQuad { min, max: min + glyph.size * scale, uv: glyph.uv });
+                }
+
+                pen += glyph.advance;
+                previous = Some(character);
+            }
+        }
+
+        quads
+    }
+
+    /// width and height of the laid out text, in units of `style.size`
+    pub fn measure(&self, text: &str, style: &TextStyle) -> Vec2 {
+        let scale = style.size / self.settings.size;
+        let lines = self.wrap(text, style);
+        let width = lines.iter().map(|line| self.line_width(line) * scale).fold(0.0, f32::max);
+        let line_height = (self.ascent - self.descent + self.line_gap) * scale * style.line_spacing;
+
+        vec2(width, lines.len() as f32 * line_height)
+    }
+
+    /// splits on new lines and, with a `max_width`, between words
+    fn wrap<'t>(&self, text: &'t str, style: &TextStyle) -> Vec<&'t str> {
+        let mut lines = vec![];
+
+        for paragraph in text.split('\n') {
+            let Some(max_width) = style.max_width else {
+                lines.push(paragraph);
+                continue;
+            };
+            let max_width = max_width / (style.size / self.settings.size);
+
+            let mut start = 0;
+            let mut last_break = None;
+            for (i, character) in paragraph.char_indices() {
+                if character == ' ' {
+                    last_break = Some(i);
+                }
+
+                let end = i + character.len_utf8();
+                if self.line_width(&paragraph[start..end]) > max_width {
+                    if let Some(space) = last_break.filter(|&space| space > start) {
+                        lines.push(&paragraph[start..space]);
+                        start = space + 1;
+                        last_break = None;
+                    }
+                }
+            }
+            lines.push(&paragraph[start..]);
+        }
+
+        lines
+    }
+
+    /// in pixels at the rasterized size
+    fn line_width(&self, line: &str) -> f32 {
+        let mut width = 0.0;
+        let mut previous = None;
+
+        for character in line.chars() {
+            let Some(glyph) = self.glyphs.get(&character) else { continue; };
+            if let Some(previous) = previous {
+                width += self.font.horizontal_kern(previous, character, self.settings.size).unwrap_or(0.0);
+            }
+            width += glyph.advance;
+            previous = Some(character);
+        }
+
+        width
+    }
+}
+
+impl Drop for Font {
+    fn drop(&mut self) {
+        unsafe {
+            DeleteTextures(1, &self.texture);
+        }
+    }
+}
+
+/// 0.5 on the outline, going to 0 and 1 `spread` pixels outside and inside of it
+fn signed_distance_field(alpha: &[u8], width: u32, height: u32, spread: u32) -> Vec<u8> {
+    let (w, h, spread) = (width as i32, height as i32, spread.max(1) as i32);
+    let inside = |x: i32, y: i32| alpha[(y * w + x) as usize] >= 128;
+    let mut field = vec![0u8; alpha.len()];
+
+    for y in 0..h {
+        for x in 0..w {
+            let is_inside = inside(x, y);
+            let mut nearest = (spread * spread) as f32;
+
+            for dy in (-spread).max(-y)..=spread.min(h - 1 - y) {
+                for dx in (-spread).max(-x)..=spread.min(w - 1 - x) {
+                    if inside(x + dx, y + dy) != is_inside {
+                        nearest = nearest.min((dx * dx + dy * dy) as f32);
+                    }
+                }
+            }
+
+            let distance = nearest.sqrt() / spread as f32 * if is_inside { 1.0 } else { -1.0 };
+            field[(y * w + x) as usize] = ((0.5 + distance * 0.5).clamp(0.0, 1.0) * 255.0) as u8;
+        }
+    }
+
+    field
+}
+
+/// a laid out glyph, see `Font::layout`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphQuad {
+    /// top left and bottom right corners, y going down
+    pub min: Vec2,
+    pub max: Vec2,
+    pub uv: UvRect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    /// height of an em, in pixels on screen or in world units
+    pub size: f32,
+    pub color: Vec4,
+    /// lines are lined up to the left of, around or to the right of the text's position
+    pub align: TextAlign,
+    /// words that would go past this move to the next line, same units as `size`
+    pub max_width: Option<f32>,
+    /// multiplies the font's line height
+    pub line_spacing: f32,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            size: 32.0,
+            color: Vec4::ONE,
+            align: TextAlign::Left,
+            max_width: None,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextSpace {
+    /// drawn over everything, after post processing. the position is in pixels
+    /// from the top left corner of the viewport
+    Screen(Vec2),
+    /// a flat sign in the scene, facing +z before rotating
+    World { position: Vec3, rotation: Quat },
+    /// in the scene but always facing the camera
+    Billboard(Vec3),
+}
+
+/// a string the renderer draws every frame, see `Renderer::add_text`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Text {
+    pub text: String,
+    pub font: FontHandle,
+    pub style: TextStyle,
+    pub space: TextSpace,
+    pub hidden: bool,
+}
+
+impl Text {
+    pub fn new(text: &str, font: FontHandle, space: TextSpace) -> Self {
+        Self {
+            text: text.to_string(),
+            font,
+            style: TextStyle::default(),
+            space,
+            hidden: false,
+        }
+    }
+}
+
+pub type TextHandle = Handle<Text>;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TextVertex {
+    position: Vec3,
+    tex_coords: Vec2,
+}
+
+/// the buffer every text is streamed through
+pub(crate) struct TextBuffers {
+    vao: GLuint,
+    vbo: GLuint,
+}
+
+impl TextBuffers {
+    pub(crate) fn new() -> Self {
+        let mut buffers = Self { vao: 0, vbo: 0 };
+
+        unsafe {
+            GenVertexArrays(1, &mut buffers.vao);
+            GenBuffers(1, &mut buffers.vbo);
+
+            BindVertexArray(buffers.vao);
+            BindBuffer(ARRAY_BUFFER, buffers.vbo);
+            gen_attrib_pointers!(TextVertex, 0 => position: 3, 1 => tex_coords: 2);
+            BindVertexArray(0);
+        }
+
+        buffers
+    }
+}
+
+impl Drop for TextBuffers {
+    fn drop(&mut self) {
+        unsafe {
+            DeleteVertexArrays(1, &self.vao);
+            DeleteBuffers(1, &self.vbo);
+        }
+    }
+}
+
+impl Renderer {
+    pub fn add_font(&mut self, font: Font) -> Option<FontHandle> {
+        Some(self.fonts.insert(font))
+    }
+
+    pub fn destroy_font(&mut self, handle: FontHandle) {
+        if self.fonts.remove(handle).is_none() {
+            println!("Failed to remove font");
+        }
+    }
+
+    pub fn add_text(&mut self, text: Text) -> Option<TextHandle> {
+        let Some(font) = self.fonts.get_mut(text.font) else {
+            println!("Failed to add text, its font doesn't exist");
+            return None;
+        };
+        font.cache_glyphs(&text.text);
+
+        Some(self.texts.insert(text))
+    }
+
+    pub fn destroy_text(&mut self, handle: TextHandle) {
+        if self.texts.remove(handle).is_none() {
+            println!("Failed to remove text");
+        }
+    }
+
+    /// rasterizes characters that were added to texts since the last frame
+    pub(crate) fn update_texts(&mut self) {
+        for text in self.texts.values() {
+            if let Some(font) = self.fonts.get_mut(text.font) {
+                font.cache_glyphs(&text.text);
+            }
+        }
+    }
+
+    /// draws either the texts placed in the scene or the ones on the screen
+    pub(crate) unsafe fn draw_texts(&self, screen: bool) {
+        let mut viewport = [0; 4];
+        GetIntegerv(VIEWPORT, viewport.as_mut_ptr());
+        let screen_matrix = Mat4::orthographic_rh_gl(0.0, viewport[2] as f32, viewport[3] as f32, 0.0, -1.0, 1.0);
+        let view_proj = self.camera.proj * self.camera.view;
+
+        let depth_test = IsEnabled(DEPTH_TEST) == TRUE;
+        let cull_face = IsEnabled(CULL_FACE) == TRUE;
+        let blend = BlendState::current();
+        let mut depth_mask = 0;
+        GetBooleanv(DEPTH_WRITEMASK, &mut depth_mask);
+        Disable(CULL_FACE);
+        if screen { Disable(DEPTH_TEST); }
+        DepthMask(FALSE);
+        Enable(BLEND);
+        BlendFunc(SRC_ALPHA, ONE_MINUS_SRC_ALPHA);
+
+        let shader = *TEXT_SHADER;
+        shader.use_shader();
+        shader.uniform_sampler(cstr!("glyphs"), 0);
+        ActiveTexture(TEXTURE0);
+        BindVertexArray(self.text_buffers.vao);
+        BindBuffer(ARRAY_BUFFER, self.text_buffers.vbo);
+
+        let mut vertices = vec![];
+        for text in self.texts.values() {
+            if text.hidden || matches!(text.space, TextSpace::Screen(_)) != screen {
+                continue;
+            }
+            let Some(font) = self.fonts.get(text.font) else { continue; };
+
+            // layouts go down from the top, the scene goes up
+            let transform = match text.space {
+                TextSpace::Screen(position) => screen_matrix * Mat4::from_translation(position.extend(0.0)),
+                TextSpace::World { position, rotation } => {
+                    view_proj * Mat4::from_rotation_translation(rotation, position) * Mat4::from_scale(Vec3::new(1.0, -1.0, 1.0))
+                }
+                TextSpace::Billboard(position) => {
+                    let facing = Mat3::from_mat4(self.camera.view).transpose();
+                    view_proj * Mat4::from_translation(position) * Mat4::from_mat3(facing) * Mat4::from_scale(Vec3::new(1.0, -1.0, 1.0))
+                }
+            };
+
+            vertices.clear();
+            for quad in font.layout(&text.text, &text.style) {
+                let corners = [
+                    (quad.min, vec2(quad.uv.min.x, quad.uv.max.y)),
+                    (vec2(quad.max.x, quad.min.y), quad.uv.max),
+                    (quad.max, vec2(quad.uv.max.x, quad.uv.min.y)),
+                    (vec2(quad.min.x, quad.max.y), quad.uv.min),
+                ];
+                for i in [0, 1, 2, 2, 3, 0] {
+                    vertices.push(TextVertex { position: corners[i].0.extend(0.0), tex_coords: corners[i].1 });
+                }
+            }
+            if vertices.is_empty() {
+                continue;
+            }
+
+            BufferData(
+                ARRAY_BUFFER,
+                (vertices.len() * size_of::<TextVertex>()) as isize,
+                vertices.as_ptr() as *const std::ffi::c_void,
+                STREAM_DRAW,
+            );
+
+            shader.uniform_mat4fv(cstr!("transform"), &transform.to_cols_array());
+            shader.uniform_vec4f(cstr!("color"), &text.style.color);
+            shader.uniform_1i(cstr!("sdf"), font.settings.sdf as i32);
+            BindTexture(TEXTURE_2D, font.texture);
+
+            DrawArrays(TRIANGLES, 0, vertices.len() as GLsizei);
+        }
+
+        BindTexture(TEXTURE_2D, 0);
+        BindVertexArray(0);
+        UseProgram(0);
+        DepthMask(depth_mask);
+        if depth_test { Enable(DEPTH_TEST); }
+        if cull_face { Enable(CULL_FACE); }
+        blend.restore();
+    }
+}
@@ -0,0 +1,298 @@
+use crate::{bind_buffer, cstr, gen_attrib_pointers, Arena, BlendMode, Handle, MaterialHandle, Mesh, RenderQueue, Renderer, Vertex};
+
+use std::mem::{offset_of, size_of};
+use std::ptr;
+
+use std::ffi::CString;
+
+use gl::{*, types::GLsizei};
+use glam::{Mat4, Quat, Vec3, Vec4};
+
+/// a single copy of an instanced mesh, with its own transform and color
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Instance {
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+    pub color: Vec4,
+
+    /// left out of the instance buffer, the culler never changes it
+    pub hidden: bool,
+    /// outside the camera's frustum, set by the culler
+    pub(crate) culled: bool,
+}
+
+impl Instance {
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            position,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+            color: Vec4::ONE,
+            hidden: false,
+            culled: false,
+        }
+    }
+
+    pub fn model_matrix(&self) -> Mat4 {
+        Mat4::from_translation(self.position) *
+        Mat4::from_quat(self.rotation) *
+        Mat4::from_scale(self.scale)
+    }
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self::new(Vec3::ZERO)
+    }
+}
+
+/* what actually goes into the instance buffer */
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct InstanceData {
+    model: Mat4,
+    color: Vec4,
+}
+
+pub type InstanceHandle = Handle<Instance>;
+
+/// a mesh drawn many times with a single draw call. every instance gets its
+/// own transform and color, which are uploaded to a per-instance buffer
+#[derive(PartialEq, Debug, Clone)]
+pub struct InstancedMesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+
+    pub instances: Arena<Instance>,
+
+    pub vao: u32,
+    pub ebo: u32,
+    pub vbo: u32,
+    pub instance_vbo: u32,
+
+    /// drawn with the renderer's default instanced material when None.
+    /// the material's shader has to read the per instance attributes
+    pub material: Option<MaterialHandle>,
+
+    /// amount of instances that are drawn, as of the last upload
+    pub visible_instances: usize,
+    /// the visible instances plus the culled ones, which still cast shadows
+    pub shadow_instances: usize,
+    /// set whenever the instances change so the renderer knows to re-upload them
+    pub needs_upload: bool,
+
+    pub has_been_set_up: bool,
+    pub hidden: bool,
+    pub cast_shadows: bool,
+    pub receive_shadows: bool,
+    /// which queue it's drawn in, see `RenderQueue`
+    pub queue: RenderQueue,
+    /// the queue's default blend mode when None
+    pub blend: Option<BlendMode>,
+}
+
+impl InstancedMesh {
+    pub fn new(vertices: &Vec<Vertex>, indices: &Vec<u32>) -> Self {
+        Self {
+            vertices: vertices.to_vec(), indices: indices.to_vec(),
+            instances: Arena::new(),
+            vao: 0, vbo: 0, ebo: 0, instance_vbo: 0,
+            material: None,
+            visible_instances: 0,
+            shadow_instances: 0,
+            needs_upload: true,
+            has_been_set_up: false,
+            hidden: false,
+            cast_shadows: true,
+            receive_shadows: true,
+            queue: RenderQueue::Opaque,
+            blend: None,
+        }
+    }
+
+    pub fn set_material(&mut self, material: MaterialHandle) {
+        self.material = Some(material);
+    }
+
+    pub fn add_instance(&mut self, instance: Instance) -> InstanceHandle {
+        let handle = self.instances.insert(instance);
+        self.needs_upload = true;
+
+        handle
+    }
+
+    pub fn get_instance(&self, handle: InstanceHandle) -> Option<&Instance> {
+        self.instances.get(handle)
+    }
+
+    pub fn get_instance_mut(&mut self, handle: InstanceHandle) -> Option<&mut Instance> {
+        self.needs_upload = true;
+        self.instances.get_mut(handle)
+    }
+
+    pub fn update_instance(&mut self, handle: InstanceHandle, instance: Instance) {
+        if let Some(old) = self.instances.get_mut(handle) {
+            *old = instance;
+            self.needs_upload = true;
+        } else {
+            println!("Failed to update instance, there is no instance with handle {:?}", handle);
+        }
+    }
+
+    pub fn remove_instance(&mut self, handle: InstanceHandle) {
+        if self.instances.remove(handle).is_some() {
+            self.needs_upload = true;
+        } else {
+            println!("Failed to remove instance, or there was no instance to remove");
+        }
+    }
+
+    pub fn setup_mesh(&mut self) {
+        unsafe {
+            GenVertexArrays(1, &mut self.vao);
+            GenBuffers(1, &mut self.vbo);
+            GenBuffers(1, &mut self.ebo);
+            GenBuffers(1, &mut self.instance_vbo);
+
+            BindVertexArray(self.vao);
+
+            bind_buffer!(ARRAY_BUFFER, self.vbo, self.vertices);
+            bind_buffer!(ELEMENT_ARRAY_BUFFER, self.ebo, self.indices);
+            gen_attrib_pointers!(Vertex, 0 => position: 3, 1 => color: 4, 2 => tex_coords: 2, 3 => normal: 3);
+
+            // per instance attributes, a mat4 takes up 4 consecutive locations
+            BindBuffer(ARRAY_BUFFER, self.instance_vbo);
+            let stride = size_of::<InstanceData>() as GLsizei;
+            let model_offset = offset_of!(InstanceData, model);
+            for i in 0..4 {
+                let location = 4 + i;
+                EnableVertexAttribArray(location);
+                VertexAttribPointer(
+                    location, 4, FLOAT, FALSE, stride,
+                    (model_offset + i as usize * size_of::<Vec4>()) as *const std::ffi::c_void
+                );
+                VertexAttribDivisor(location, 1);
+            }
+
+            EnableVertexAttribArray(8);
+            VertexAttribPointer(8, 4, FLOAT, FALSE, stride, offset_of!(InstanceData, color) as *const std::ffi::c_void);
+            VertexAttribDivisor(8, 1);
+
+            BindVertexArray(0);
+        }
+
+        self.upload_instances();
+    }
+
+    /// packs every instance that is not hidden tightly into the instance buffer,
+    /// the culled ones last so they are only drawn into the shadow maps
+    pub fn upload_instances(&mut self) {
+        let (culled, visible): (Vec<&Instance>, Vec<&Instance>) = self.instances.values()
+            .filter(|instance| !instance.hidden)
+            .partition(|instance| instance.culled);
+        let visible_instances = visible.len();
+
+        let data = visible.into_iter().chain(culled)
+            .map(|instance| InstanceData {
+                model: instance.model_matrix(),
+                color: instance.color
+            })
+            .collect::<Vec<InstanceData>>();
+
+        unsafe {
+            BindBuffer(ARRAY_BUFFER, self.instance_vbo);
+            BufferData(
+                ARRAY_BUFFER,
+                (data.len() * size_of::<InstanceData>()) as isize,
+                if data.is_empty() { ptr::null() } else { data.as_ptr() as *const std::ffi::c_void },
+                STREAM_DRAW,
+            );
+            BindBuffer(ARRAY_BUFFER, 0);
+        }
+
+        self.visible_instances = visible_instances;
+        self.shadow_instances = data.len();
+        self.needs_upload = false;
+    }
+
+    pub unsafe fn draw(&self, renderer: &Renderer) {
+        if self.hidden || self.visible_instances == 0 { return; }
+
+        let material = renderer.material_or(self.material, renderer.default_instanced_material);
+
+        BindVertexArray(self.vao);
+        material.bind();
+
+        material.shader.uniform_1i(cstr!("receive_shadows"), self.receive_shadows as i32);
+
+        DrawElementsInstanced(
+            TRIANGLES,
+            self.indices.len() as i32,
+            UNSIGNED_INT,
+            ptr::null(),
+            self.visible_instances as i32
+        );
+
+        BindVertexArray(0);
+        UseProgram(0);
+    }
+
+    /// draws only the geometry with an already bound instanced depth shader, for shadow maps
+    pub unsafe fn draw_depth(&self) {
+        if self.hidden || !self.cast_shadows || self.shadow_instances == 0 { return; }
+
+        BindVertexArray(self.vao);
+        DrawElementsInstanced(
+            TRIANGLES,
+            self.indices.len() as i32,
+            UNSIGNED_INT,
+            ptr::null(),
+            self.shadow_instances as i32
+        );
+        BindVertexArray(0);
+    }
+}
+
+impl Mesh {
+    /// creates an instanced mesh out of this mesh's geometry, with no instances.
+    /// the material is not carried over since it needs an instancing shader
+    pub fn to_instanced(&self) -> InstancedMesh {
+        InstancedMesh::new(&self.vertices, &self.indices)
+    }
+}
+
+impl Renderer {
+    pub fn add_instanced_mesh(&mut self, mesh: InstancedMesh) -> Option<InstancedMeshHandle> {
+        Some(self.instanced_meshes.insert(mesh))
+    }
+
+    pub fn get_instanced_mesh_mut(&mut self, handle: InstancedMeshHandle) -> Option<&mut InstancedMesh> {
+        self.instanced_meshes.get_mut(handle)
+    }
+
+    pub fn get_instanced_mesh(&self, handle: InstancedMeshHandle) -> Option<&InstancedMesh> {
+        self.instanced_meshes.get(handle)
+    }
+
+    pub fn destroy_instanced_mesh(&mut self, handle: InstancedMeshHandle) {
+        if self.instanced_meshes.remove(handle).is_some() {
+
+        } else {
+            println!("Failed to remove instanced mesh, or there was no mesh to remove");
+        }
+    }
+}
+
+impl Drop for InstancedMesh {
+    fn drop(&mut self) {
+        unsafe {
+            DeleteVertexArrays(1, &self.vao);
+            DeleteBuffers(1, &self.ebo);
+            DeleteBuffers(1, &self.vbo);
+            DeleteBuffers(1, &self.instance_vbo);
+        }
+    }
+}
+
+pub type InstancedMeshHandle = Handle<InstancedMesh>;
@@ -1,65 +1,132 @@
 use glam::Vec3;
 
-use std::{collections::HashMap, ffi::CString, ops::{Index, IndexMut}};
+use crate::{Handle, Renderer, ShadowSettings};
 
-use crate::{cstr, Renderer, Shader};
+/// lights past this many are ignored. the whole `Lights` uniform block has to fit
+/// in 16KB, the smallest block size opengl guarantees, so don't go much higher
+pub const MAX_LIGHTS: usize = 128;
 
-#[derive(Copy, Clone)]
-pub struct Light {
-    pub position: Vec3,
-    pub color: Vec3,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    /// infinitely far away, like the sun. the position is ignored
+    Directional { direction: Vec3 },
+    Point,
+    /// cone angles are measured from the direction to the edge, in radians.
+    /// the light fades out between the inner and the outer cone
+    Spot { direction: Vec3, inner_cone: f32, outer_cone: f32 },
 }
-#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
-pub struct LightHandle {
-    pub id: usize,
+
+impl LightKind {
+    /* matches the constants in DEFAULT_FS */
+    pub(crate) fn id(&self) -> f32 {
+        match self {
+            LightKind::Directional { .. } => 0.0,
+            LightKind::Point => 1.0,
+            LightKind::Spot { .. } => 2.0,
+        }
+    }
 }
 
-impl Index<LightHandle> for HashMap<LightHandle, Light> {
-    type Output = Light;
+/// 1 / (constant + linear * d + quadratic * d²)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
 
-    fn index(&self, handle: LightHandle) -> &Self::Output {
-        self.get(&handle).expect("No entry found for key")
+impl Default for Attenuation {
+    fn default() -> Self {
+        Self {
+            constant: 1.0,
+            linear: 0.045,
+            quadratic: 0.016,
+        }
     }
 }
 
-impl IndexMut<LightHandle> for HashMap<LightHandle, Light> {
-    fn index_mut(&mut self, handle: LightHandle) -> &mut Self::Output {
-        self.get_mut(&handle).expect("No entry found for key")
-    }
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    pub position: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+    /// point and spot lights smoothly fade to nothing at this distance
+    pub range: f32,
+    pub attenuation: Attenuation,
+
+    /// None if the light should not cast shadows
+    pub shadow: Option<ShadowSettings>,
 }
 
-impl Renderer {
-    // i'm assuming shader.use_shader() has been called
-    pub unsafe fn send_light_uniforms(&self, shader: &Shader) {
-        shader.uniform_vec3f(cstr!("viewPos"), &self.camera.pos);
-        shader.uniform_1i(cstr!("num_lights"), self.lights.len() as i32);
-        let mut i = 0;
+impl Light {
+    pub fn point(position: Vec3, color: Vec3) -> Self {
+        Self {
+            position,
+            color,
+            ..Default::default()
+        }
+    }
 
-        for light in self.lights.values() {
-            shader.uniform_vec3f(cstr!(format!("lightColor[{}]", i)), &light.color);
-            shader.uniform_vec3f(cstr!(format!("lightPos[{}]", i)), &light.position);
+    pub fn directional(direction: Vec3, color: Vec3) -> Self {
+        Self {
+            kind: LightKind::Directional { direction },
+            color,
+            ..Default::default()
+        }
+    }
 
-            i+=1;
+    pub fn spot(position: Vec3, direction: Vec3, color: Vec3, inner_cone: f32, outer_cone: f32) -> Self {
+        Self {
+            kind: LightKind::Spot { direction, inner_cone, outer_cone },
+            position,
+            color,
+            ..Default::default()
         }
     }
 
-    pub fn add_light(&mut self, light: Light) -> Option<LightHandle> {
-        let handle = LightHandle {id: self.lights.len()};
+    pub(crate) fn direction(&self) -> Vec3 {
+        match self.kind {
+            LightKind::Directional { direction } | LightKind::Spot { direction, .. } => direction.normalize_or_zero(),
+            LightKind::Point => Vec3::ZERO,
+        }
+    }
 
-        if self.lights.contains_key(&handle) {
-            println!("Light with handle {:?} already exists", handle);
-            return None;
+    /// cosines of the inner and outer cone, a point light is a spot light that covers everything
+    pub(crate) fn cone(&self) -> (f32, f32) {
+        match self.kind {
+            LightKind::Spot { inner_cone, outer_cone, .. } => (inner_cone.cos(), outer_cone.cos()),
+            _ => (-1.0, -1.0),
         }
+    }
+}
 
-        self.lights.insert(handle, light);
-        Some(handle)
+impl Default for Light {
+    fn default() -> Self {
+        Self {
+            kind: LightKind::Point,
+            position: Vec3::ZERO,
+            color: Vec3::ONE,
+            intensity: 1.0,
+            range: 1000.0,
+            attenuation: Attenuation::default(),
+            shadow: None,
+        }
+    }
+}
+
+pub type LightHandle = Handle<Light>;
+
+impl Renderer {
+    pub fn add_light(&mut self, light: Light) -> Option<LightHandle> {
+        Some(self.lights.insert(light))
     }
 
     pub fn destroy_light(&mut self, handle: LightHandle) {
-        if self.lights.remove(&handle).is_some() {
+        if self.lights.remove(handle).is_some() {
 
         } else {
             println!("Failed to remove light");
         }
     }
-}
\ No newline at end of file
+}
@@ -1,10 +1,8 @@
-#![allow(deref_nullptr)]
-
 #[macro_export]
 macro_rules! gen_attrib_pointers {
     ($struct_name:ident, $($index:expr => $field_name:ident: $dimension:expr),*) => {
         $(
-            let offset = &((*std::ptr::null::<$struct_name>()).$field_name) as *const _ as *const std::ffi::c_void;
+            let offset = std::mem::offset_of!($struct_name, $field_name) as *const std::ffi::c_void;
             EnableVertexAttribArray($index);
             VertexAttribPointer($index, $dimension, FLOAT, FALSE, size_of::<$struct_name>() as GLsizei, offset);
         )*
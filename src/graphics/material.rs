@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+
+use gl::*;
+use gl::types::{GLenum, GLuint};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+
+use crate::{cstr, CubemapHandle, Handle, Renderer, Shader, TextureHandle, DEFAULT_SHADER, ENVIRONMENT_TEXTURE_UNIT};
+
+/// a value that gets uploaded to the uniform with the same name
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum MaterialParam {
+    Float(f32),
+    Int(i32),
+    Vec2(Vec2),
+    Vec3(Vec3),
+    Vec4(Vec4),
+    Mat4(Mat4),
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct TextureSlot {
+    /// name of the sampler uniform in the shader
+    pub name: String,
+    pub texture: GLuint,
+    /// TEXTURE_2D or TEXTURE_CUBE_MAP
+    pub target: GLenum,
+}
+
+/// a shader together with the uniforms and textures it is drawn with.
+/// meshes reference materials by handle so many of them can share one
+#[derive(PartialEq, Debug, Clone)]
+pub struct Material {
+    pub shader: Shader,
+    pub params: HashMap<String, MaterialParam>,
+    pub textures: Vec<TextureSlot>,
+}
+
+pub type MaterialHandle = Handle<Material>;
+
+impl Material {
+    /// a material with no parameters, for custom shaders
+    pub fn new(shader: Shader) -> Self {
+        Self {
+            shader,
+            params: HashMap::new(),
+            textures: vec![],
+        }
+    }
+
+    pub fn set_param(&mut self, name: &str, param: MaterialParam) {
+        self.params.insert(name.to_string(), param);
+    }
+
+    pub fn get_param(&self, name: &str) -> Option<&MaterialParam> {
+        self.params.get(name)
+    }
+
+    /* shorthands for the parameters the built in shaders use */
+    /// the tint, multiplied with the vertex colors. keeps the current alpha
+    pub fn set_color(&mut self, color: Vec3) {
+        let alpha = match self.get_param("color") {
+            Some(MaterialParam::Vec4(tint)) => tint.w,
+            _ => 1.0,
+        };
+        self.set_param("color", MaterialParam::Vec4(color.extend(alpha)));
+    }
+
+    pub fn set_alpha(&mut self, alpha: f32) {
+        let color = match self.get_param("color") {
+            Some(MaterialParam::Vec4(tint)) => tint.truncate(),
+            _ => Vec3::ONE,
+        };
+        self.set_param("color", MaterialParam::Vec4(color.extend(alpha)));
+    }
+
+    pub fn set_diffuse_texture(&mut self, texture_handle: TextureHandle, renderer: &Renderer) {
+        self.set_texture("texture1", texture_handle, renderer);
+        self.set_param("has_texture", MaterialParam::Int(1));
+    }
+
+    pub fn set_texture(&mut self, name: &str, texture_handle: TextureHandle, renderer: &Renderer) {
+        self.set_texture_id(name, renderer.textures[texture_handle]);
+    }
+
+    pub fn set_texture_id(&mut self, name: &str, texture: GLuint) {
+        self.set_texture_target(name, texture, TEXTURE_2D);
+    }
+
+    pub fn set_cubemap(&mut self, name: &str, cubemap_handle: CubemapHandle, renderer: &Renderer) {
+        self.set_texture_target(name, renderer.cubemaps[cubemap_handle].id, TEXTURE_CUBE_MAP);
+    }
+
+    pub fn set_texture_target(&mut self, name: &str, texture: GLuint, target: GLenum) {
+        if let Some(slot) = self.textures.iter_mut().find(|slot| slot.name == name) {
+            slot.texture = texture;
+            slot.target = target;
+        } else {
+            self.textures.push(TextureSlot { name: name.to_string(), texture, target });
+        }
+    }
+
+    /// how much of the environment is mirrored, from 0 to 1
+    pub fn set_reflectivity(&mut self, reflectivity: f32) {
+        self.set_param("reflectivity", MaterialParam::Float(reflectivity));
+    }
+
+    /// pixels with less alpha than this are cut out, meant for meshes in
+    /// `RenderQueue::AlphaTested` like foliage and fences
+    pub fn set_alpha_cutoff(&mut self, cutoff: f32) {
+        self.set_param("alpha_cutoff", MaterialParam::Float(cutoff));
+    }
+
+    /// reflects this cubemap instead of the skybox
+    pub fn set_environment(&mut self, cubemap_handle: CubemapHandle, renderer: &Renderer) {
+        self.set_cubemap("environment", cubemap_handle, renderer);
+    }
+
+    /// uses the shader, uploads every parameter and binds each texture to its own unit
+    pub unsafe fn bind(&self) {
+        self.shader.use_shader();
+
+        for (name, param) in &self.params {
+            let name = CString::new(name.as_str()).expect("CString conversion failed");
+            let name = name.as_c_str();
+            match param {
+                MaterialParam::Float(v) => self.shader.uniform_1f(name, *v),
+                MaterialParam::Int(v) => self.shader.uniform_1i(name, *v),
+                MaterialParam::Vec2(v) => self.shader.uniform_vec2f(name, v),
+                MaterialParam::Vec3(v) => self.shader.uniform_vec3f(name, v),
+                MaterialParam::Vec4(v) => self.shader.uniform_vec4f(name, v),
+                MaterialParam::Mat4(v) => self.shader.uniform_mat4fv(name, &v.to_cols_array()),
+            }
+        }
+
+        // a unit can't be shared by samplers of different types, so the 2D textures take
+        // the first units and everything else comes after them
+        let flat = self.textures.iter().filter(|slot| slot.target == TEXTURE_2D);
+        let other = self.textures.iter().filter(|slot| slot.target != TEXTURE_2D);
+        let mut unit = 0;
+        for slot in flat.chain(other) {
+            ActiveTexture(TEXTURE0 + unit);
+            BindTexture(slot.target, slot.texture);
+            self.shader.uniform_sampler(cstr!(slot.name.as_str()), unit);
+            unit += 1;
+        }
+
+        // without a diffuse texture, `texture1` would still point at unit 0 where a cubemap may be
+        let has_diffuse = self.textures.iter().any(|slot| slot.name == "texture1");
+        if !has_diffuse && self.shader.uniform_location(cstr!("texture1")).is_ok() {
+            self.shader.uniform_sampler(cstr!("texture1"), unit);
+        }
+
+        // samplers stick to the program, so point it back at the skybox after a material with its own environment
+        let own_environment = self.textures.iter().any(|slot| slot.name == "environment");
+        if !own_environment && self.shader.uniform_location(cstr!("environment")).is_ok() {
+            self.shader.uniform_sampler(cstr!("environment"), ENVIRONMENT_TEXTURE_UNIT);
+        }
+
+        ActiveTexture(TEXTURE0);
+    }
+
+    /// builds a material out of an .mtl entry, loading its diffuse texture
+    /// relative to `directory`. the texture id is also returned so the owner can free it
+    pub fn from_mtl(mtl: &tobj::Material, directory: &std::path::Path) -> (Self, Option<GLuint>) {
+        let mut material = Material::default();
+
+        if let Some(diffuse) = mtl.diffuse {
+            material.set_color(Vec3::from_array(diffuse));
+        }
+        if let Some(dissolve) = mtl.dissolve {
+            material.set_alpha(dissolve);
+        }
+
+        let mut texture = None;
+        if let Some(path) = &mtl.diffuse_texture {
+            let path = directory.join(path.replace('\\', "/"));
+
+            match unsafe { crate::load_texture(&path.to_string_lossy()) } {
+                Ok(id) => {
+                    material.set_texture_id("texture1", id);
+                    material.set_param("has_texture", MaterialParam::Int(1));
+                    texture = Some(id);
+                }
+                Err(err) => println!("Failed to load texture {:?} of material {}: {}", path, mtl.name, err),
+            }
+        }
+
+        (material, texture)
+    }
+}
+
+impl Default for Material {
+    /// the lit material every mesh is drawn with when it has none
+    fn default() -> Self {
+        let mut material = Material::new(*DEFAULT_SHADER);
+        material.set_color(Vec3::ONE);
+        material.set_param("has_texture", MaterialParam::Int(0));
+        material.set_reflectivity(0.0);
+        material.set_alpha_cutoff(0.0);
+
+        material
+    }
+}
+
+impl Renderer {
+    pub fn add_material(&mut self, material: Material) -> Option<MaterialHandle> {
+        Some(self.materials.insert(material))
+    }
+
+    pub fn get_material_mut(&mut self, handle: MaterialHandle) -> Option<&mut Material> {
+        self.materials.get_mut(handle)
+    }
+
+    pub fn get_material(&self, handle: MaterialHandle) -> Option<&Material> {
+        self.materials.get(handle)
+    }
+
+    /// the material behind `handle`, or the `fallback` one if there is none
+    pub fn material_or(&self, handle: Option<MaterialHandle>, fallback: MaterialHandle) -> &Material {
+        handle.and_then(|handle| self.materials.get(handle))
+            .unwrap_or(&self.materials[fallback])
+    }
+
+    pub fn destroy_material(&mut self, handle: MaterialHandle) {
+        if self.materials.remove(handle).is_some() {
+
+        } else {
+            println!("Failed to remove material");
+        }
+    }
+}
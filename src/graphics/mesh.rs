@@ -1,7 +1,6 @@
-use crate::{bind_buffer, cstr, gen_attrib_pointers, Renderer, Shader, TextureHandle, Vertex, DEFAULT_SHADER};
+use crate::{bind_buffer, cstr, gen_attrib_pointers, BlendMode, Handle, MaterialHandle, RenderQueue, Renderer, Shader, Vertex};
 
-use std::ops::{Index, IndexMut};
-use std::{collections::HashMap, ptr};
+use std::ptr;
 
 use std::ffi::CString;
 
@@ -14,21 +13,28 @@ pub struct Mesh {
     pub indices: Vec<u32>,
 
     pub vao: u32,
-    pub texture: u32,
     pub ebo: u32,
     pub vbo: u32,
 
     pub position: Vec3,
     pub rotation: Quat,
     pub scale: Vec3,
-    pub color: Vec3,
 
-    pub shader: Shader,
-    pub parent: Option<Box<Mesh>>,
-    pub children: Vec<Box<Mesh>>,
+    /// drawn with the renderer's default material when None
+    pub material: Option<MaterialHandle>,
+    /// the world matrix of the scene node owning it, kept up to date by `Renderer::update`
+    pub node_transform: Mat4,
 
     pub has_been_set_up: bool,
     pub hidden: bool,
+    /// outside the camera's frustum, set by the `Culler`. culled meshes still cast shadows
+    pub culled: bool,
+    pub cast_shadows: bool,
+    pub receive_shadows: bool,
+    /// which queue it's drawn in, see `RenderQueue`
+    pub queue: RenderQueue,
+    /// the queue's default blend mode when None
+    pub blend: Option<BlendMode>,
 }
 
 pub struct MeshData {
@@ -44,88 +50,57 @@ impl Mesh {
             position: Vec3::ZERO,
             rotation: Quat::from_euler(glam::EulerRot::XYZ, 0.0, 0.0, 0.0),
             scale: Vec3::ONE,
-            texture: 0,
-            color: Vec3::ONE,
-            shader: *DEFAULT_SHADER,
-            parent: None,
-            children: Vec::new(),
+            material: None,
+            node_transform: Mat4::IDENTITY,
             has_been_set_up: false,
             hidden: false,
+            culled: false,
+            cast_shadows: true,
+            receive_shadows: true,
+            queue: RenderQueue::Opaque,
+            blend: None,
         };
 
         mesh
     }
-    pub fn set_texture(&mut self, texture_handle: TextureHandle, renderer: &Renderer) {
-        self.texture = renderer.textures[&texture_handle];
+    pub fn set_material(&mut self, material: MaterialHandle) {
+        self.material = Some(material);
     }
-    /* 
 
-    pub fn to_instance(&mut self, data: Vec<InstanceData>, n: usize) -> InstanceMesh {
-        let mut new_mesh = InstanceMesh::new(&self.vertices, &self.indices, n);
-
-        new_mesh.instance_data = data;
-
-        unsafe { new_mesh.setup_mesh() };
-
-        drop(self);
-
-        new_mesh
-    }
-
-    pub fn set_parent(&mut self, parent: Mesh){
-        self.parent = Some(Box::new(parent));
-    }
-
-    pub fn add_child(&mut self, mut child: Mesh){
-        child.set_parent(self.clone());
-        self.children.push(Box::new(child));
-    }
-    */
-
-    pub fn set_color(&mut self, color: Vec3){
-        self.color = color;
-    }
-
-    pub fn set_position(&mut self, position: Vec3){
+    pub fn set_position(&mut self, position: Vec3) {
         self.position = position;
-        for child in self.children.as_mut_slice(){
-            child.set_position(position + child.position)
-        }
     }
 
-    pub fn add_position(&mut self, position: Vec3){
+    pub fn add_position(&mut self, position: Vec3) {
         self.position += position;
-        for child in self.children.as_mut_slice(){
-            child.add_position(position)
-        }
     }
 
-    pub fn set_scale(&mut self, scale: Vec3){
+    pub fn set_scale(&mut self, scale: Vec3) {
         self.scale = scale;
-        for child in self.children.as_mut_slice(){
-            child.set_scale(scale);
-        }
     }
 
-    pub fn scale(&mut self, scale: Vec3){
+    pub fn scale(&mut self, scale: Vec3) {
         self.scale *= scale;
-        for child in self.children.as_mut_slice(){
-            child.scale(scale);
-        }
     }
 
-    pub fn set_rotation(&mut self, rotation: Quat){
+    pub fn set_rotation(&mut self, rotation: Quat) {
         self.rotation = rotation;
-        for child in self.children.as_mut_slice(){
-            child.set_rotation(rotation);
-        }
     }
 
-    pub fn rotate(&mut self, rotation: Quat){
-        self.rotation = self.rotation + rotation;
-        for child in self.children.as_mut_slice(){
-            child.rotate(rotation);
-        }
+    pub fn rotate(&mut self, rotation: Quat) {
+        self.rotation = rotation * self.rotation;
+    }
+
+    /// position, rotation and scale on top of the transform of the node owning it
+    pub fn model_matrix(&self) -> Mat4 {
+        self.node_transform *
+        Mat4::from_translation(self.position) *
+        Mat4::from_quat(self.rotation) *
+        Mat4::from_scale(self.scale)
+    }
+
+    pub fn world_position(&self) -> Vec3 {
+        self.node_transform.transform_point3(self.position)
     }
 
     pub fn setup_mesh(&mut self) {
@@ -139,88 +114,66 @@ impl Mesh {
             bind_buffer!(ARRAY_BUFFER, self.vbo, self.vertices);
             bind_buffer!(ELEMENT_ARRAY_BUFFER, self.ebo, self.indices);
             gen_attrib_pointers!(Vertex, 0 => position: 3, 1 => color: 4, 2 => tex_coords: 2, 3 => normal: 3);
-    
-            gl::BindTexture(gl::TEXTURE_2D, self.texture);
 
             BindVertexArray(0);
         }
     }
     
     pub unsafe fn draw(&self, renderer: &Renderer) {
-        if self.hidden { return; }
+        if self.hidden || self.culled { return; }
+
+        let model_matrix = self.model_matrix();
 
-        let model_matrix = 
-            Mat4::from_translation(self.position) *
-            Mat4::from_quat(self.rotation) *
-            Mat4::from_scale(self.scale);
+        let material = renderer.material_or(self.material, renderer.default_material);
 
         BindVertexArray(self.vao);
-        self.shader.use_shader();
-        
-        if self.texture != 0 {
-            unsafe {
-                self.shader.uniform_1i(cstr!("has_texture"), 1);
-            }
-        } else {
-            unsafe {
-                self.shader.uniform_1i(cstr!("has_texture"), 0);
-            }
-        }
-        
-        BindTexture(TEXTURE_2D, self.texture);
+        material.bind();
 
         // Set uniforms and draw
-        self.shader.uniform_mat4fv(cstr!("model"), &model_matrix.to_cols_array());
-        self.shader.uniform_vec3f(cstr!("pos"), &self.position);
-        self.shader.uniform_vec3f(cstr!("color"), &self.color);
-        renderer.send_light_uniforms(&self.shader);
-        renderer.camera.send_uniforms(&self.shader);
+        material.shader.uniform_mat4fv(cstr!("model"), &model_matrix.to_cols_array());
+        material.shader.uniform_1i(cstr!("receive_shadows"), self.receive_shadows as i32);
         
         DrawElements(TRIANGLES, self.indices.len() as i32, UNSIGNED_INT, ptr::null());
         
         BindVertexArray(0);
         UseProgram(0);
     }
+
+    /// draws only the geometry with an already bound depth shader, for shadow maps
+    pub unsafe fn draw_depth(&self, shader: &Shader) {
+        if self.hidden || !self.cast_shadows { return; }
+
+        let model_matrix = self.model_matrix();
+
+        shader.uniform_mat4fv(cstr!("model"), &model_matrix.to_cols_array());
+
+        BindVertexArray(self.vao);
+        DrawElements(TRIANGLES, self.indices.len() as i32, UNSIGNED_INT, ptr::null());
+        BindVertexArray(0);
+    }
 }
 
 impl Renderer {
     pub fn add_mesh_from_vertices_and_indices(&mut self, vertices: Vec<Vertex>, indices: Vec<u32>) -> Option<MeshHandle> {
-        let handle = MeshHandle {id: self.meshes.len()};
-
-        if self.meshes.contains_key(&handle) {
-            println!("Mesh with handle {:?} already exists", handle);
-            return None;
-        }
-
         let mesh = Mesh::new(&vertices, &indices);
-        
-        self.meshes.insert(handle, mesh);
 
-        Some(handle)
+        Some(self.meshes.insert(mesh))
     }
 
     pub fn add_mesh(&mut self, mesh: Mesh) -> Option<MeshHandle> {
-        let handle = MeshHandle {id: self.meshes.len()};
-
-        if self.meshes.contains_key(&handle) {
-            println!("Mesh with handle {:?} already exists", handle);
-            return None;
-        }
-
-        self.meshes.insert(handle, mesh);
-        Some(handle)
+        Some(self.meshes.insert(mesh))
     }
 
     pub fn get_mesh_mut(&mut self, handle: MeshHandle) -> Option<&mut Mesh> {
-        self.meshes.get_mut(&handle)
+        self.meshes.get_mut(handle)
     }
 
     pub fn get_mesh(&self, handle: MeshHandle) -> Option<&Mesh> {
-        self.meshes.get(&handle)
+        self.meshes.get(handle)
     }
 
     pub fn destroy_mesh(&mut self, handle: MeshHandle) {
-        if self.meshes.remove(&handle).is_some() {
+        if self.meshes.remove(handle).is_some() {
 
         } else {
             println!("Failed to remove mesh, or there was no mesh to remove");
@@ -238,21 +191,4 @@ impl Drop for Mesh {
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
-pub struct MeshHandle {
-    pub id: usize,
-}
-
-impl Index<MeshHandle> for HashMap<MeshHandle, Mesh> {
-    type Output = Mesh;
-
-    fn index(&self, handle: MeshHandle) -> &Self::Output {
-        self.get(&handle).expect("No entry found for key")
-    }
-}
-
-impl IndexMut<MeshHandle> for HashMap<MeshHandle, Mesh> {
-    fn index_mut(&mut self, handle: MeshHandle) -> &mut Self::Output {
-        self.get_mut(&handle).expect("No entry found for key")
-    }
-}
\ No newline at end of file
+pub type MeshHandle = Handle<Mesh>;
\ No newline at end of file
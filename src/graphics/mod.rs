@@ -1,25 +1,60 @@
 mod renderer;
 mod mesh;
+mod instanced_mesh;
 mod vertex;
 mod shader;
+mod preprocessor;
 mod texture;
+mod atlas;
+mod sprite_batch;
+mod font;
+mod debug_draw;
+mod debug_view;
+mod render_queue;
+mod scene;
+mod cubemap;
+mod atmosphere;
+mod render_target;
+mod post_process;
+mod screenshot;
+mod recorder;
 mod r#macro;
 mod SHADERS;
 mod camera;
 mod light;
+mod shadow;
+mod material;
+mod uniform_buffer;
 mod model;
 mod skeletal_mesh;
 mod animation;
 
 pub use model::*;
 pub use light::*;
+pub use shadow::*;
+pub use material::*;
+pub use uniform_buffer::*;
 pub use camera::*;
 pub use SHADERS::*;
 pub use r#macro::*;
 pub use mesh::*;
+pub use instanced_mesh::*;
 pub use renderer::*;
 pub use vertex::*;
 pub use shader::*;
+pub use preprocessor::*;
 pub use skeletal_mesh::*;
 pub use texture::*;
+pub use atlas::*;
+pub use sprite_batch::*;
+pub use font::*;
+pub use debug_draw::*;
+pub use debug_view::*;
+pub use render_queue::*;
+pub use scene::*;
+pub use cubemap::*;
+pub use atmosphere::*;
+pub use render_target::*;
+pub use post_process::*;
+pub use recorder::*;
 pub use animation::*;
\ No newline at end of file
@@ -1,4 +1,4 @@
-use std::{cmp::Ordering, collections::HashMap, hash::Hash, ops::{Index, IndexMut}, path::Path};
+use std::{cmp::Ordering, path::Path};
 
 use glam::{vec2, vec3, vec4, Mat4, Vec2, Vec3, Vec4};
 #[cfg(feature = "skeletal")]
@@ -6,31 +6,21 @@ use russimp::{bone::Bone, scene::{PostProcess, Scene}, Matrix4x4};
 use tobj::LoadOptions;
 use gl::types::GLuint;
 
-use crate::{Mesh, Renderer, SkeletalMesh, SkeletalVertex, Texture, Vertex, MAX_BONE_INFLUENCE};
+use crate::{BlendMode, Handle, Material, MaterialHandle, MaterialParam, RenderQueue, Mesh, Renderer, SkeletalMesh, SkeletalVertex, Texture, Vertex, MAX_BONE_INFLUENCE};
 
-#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
-pub struct ModelHandle {
-    pub id: usize,
-}
-
-impl Index<ModelHandle> for HashMap<ModelHandle, Model> {
-    type Output = Model;
-
-    fn index(&self, handle: ModelHandle) -> &Self::Output {
-        self.get(&handle).expect("No entry found for key")
-    }
-}
-
-impl IndexMut<ModelHandle> for HashMap<ModelHandle, Model> {
-    fn index_mut(&mut self, handle: ModelHandle) -> &mut Self::Output {
-        self.get_mut(&handle).expect("No entry found for key")
-    }
-}
+pub type ModelHandle = Handle<Model>;
 
 #[derive(Default)]
 pub struct Model {
     pub meshes: Vec<Mesh>,
     pub loaded_textures: Vec<GLuint>,
+
+    /// materials read from the .mtl file, moved into the renderer by `add_model`
+    pub materials: Vec<Material>,
+    /// which of `materials` each mesh uses
+    pub mesh_materials: Vec<Option<usize>>,
+    /// handles of the materials once they have been added to the renderer
+    pub material_handles: Vec<MaterialHandle>,
 }
 
 impl Model {
@@ -45,6 +35,9 @@ impl Model {
         Self {
             meshes: vec![],
             loaded_textures: vec![],
+            materials: vec![],
+            mesh_materials: vec![],
+            material_handles: vec![],
         }
     }
 
@@ -59,7 +52,22 @@ impl Model {
         };
 
         let obj = tobj::load_obj(path, &load_options).expect("Failed to load OBJ file");
-        let (models, _) = obj;
+        let (models, materials) = obj;
+
+        let directory = path.parent().unwrap_or(Path::new(""));
+        match materials {
+            Ok(materials) => {
+                for mtl in &materials {
+                    let (material, texture) = Material::from_mtl(mtl, directory);
+                    if let Some(texture) = texture {
+                        self.loaded_textures.push(texture);
+                    }
+
+                    self.materials.push(material);
+                }
+            }
+            Err(e) => println!("Failed to load materials of {:?}: {}", path, e),
+        }
 
         for model in models {
             let mesh = &model.mesh;
@@ -103,6 +111,7 @@ impl Model {
             }
 
             self.meshes.push(final_mesh);
+            self.mesh_materials.push(mesh.material_id);
         }
     }
 
@@ -115,6 +124,30 @@ impl Model {
         texture
     }
 
+    pub fn set_cast_shadows(&mut self, cast_shadows: bool) {
+        for mesh in &mut self.meshes {
+            mesh.cast_shadows = cast_shadows;
+        }
+    }
+
+    pub fn set_receive_shadows(&mut self, receive_shadows: bool) {
+        for mesh in &mut self.meshes {
+            mesh.receive_shadows = receive_shadows;
+        }
+    }
+
+    pub fn set_queue(&mut self, queue: RenderQueue) {
+        for mesh in &mut self.meshes {
+            mesh.queue = queue;
+        }
+    }
+
+    pub fn set_blend(&mut self, blend: Option<BlendMode>) {
+        for mesh in &mut self.meshes {
+            mesh.blend = blend;
+        }
+    }
+
     pub unsafe fn draw(&self, renderer: &Renderer) {
         for mesh in &self.meshes {
             mesh.draw(renderer);
@@ -210,20 +243,34 @@ pub fn convert_russimp_mat_to_glam_mat(mat: Matrix4x4) -> Mat4 {
 }
 
 impl Renderer {
-    pub fn add_model(&mut self, model: Model) -> Option<ModelHandle> {
-        let handle = ModelHandle {id: self.models.len()};
+    pub fn add_model(&mut self, mut model: Model) -> Option<ModelHandle> {
+        model.material_handles = model.materials.drain(..)
+            .map(|material| self.materials.insert(material))
+            .collect();
+
+        for (mesh, material_id) in model.meshes.iter_mut().zip(&model.mesh_materials) {
+            if let Some(id) = material_id {
+                mesh.material = model.material_handles.get(*id).copied();
+            }
 
-        if self.models.contains_key(&handle) {
-            println!("Model with handle {:?} already exists", handle);
-            return None;
+            // see-through materials from the file, like glass
+            let translucent = mesh.material
+                .and_then(|handle| self.materials.get(handle))
+                .is_some_and(|material| matches!(material.get_param("color"), Some(MaterialParam::Vec4(color)) if color.w < 1.0));
+            if translucent {
+                mesh.queue = RenderQueue::Transparent;
+            }
         }
 
-        self.models.insert(handle, model);
-        Some(handle)
+        Some(self.models.insert(model))
     }
 
     pub fn destroy_model(&mut self, handle: ModelHandle) {
-        self.models.remove(&handle);
+        if let Some(model) = self.models.remove(handle) {
+            for material in &model.material_handles {
+                self.materials.remove(*material);
+            }
+        }
     }
 }
 
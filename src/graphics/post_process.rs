@@ -0,0 +1,328 @@
+use gl::*;
+use gl::types::{GLint, GLuint};
+use glam::{vec2, Vec2};
+
+use crate::{cstr, RenderTarget, RenderTargetSettings, Renderer, Shader, POST_BLUR_SHADER, POST_BRIGHT_SHADER, POST_COMPOSITE_SHADER, POST_FXAA_SHADER};
+
+use std::ffi::CString;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tonemapping {
+    /// colors above 1 are clipped
+    None,
+    Reinhard,
+    Aces,
+}
+
+impl Tonemapping {
+    /* matches the constants in POST_COMPOSITE_FS */
+    fn id(&self) -> i32 {
+        match self {
+            Tonemapping::None => 0,
+            Tonemapping::Reinhard => 1,
+            Tonemapping::Aces => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BloomSettings {
+    /// only what is brighter than this glows
+    pub threshold: f32,
+    pub intensity: f32,
+    /// each pass blurs horizontally and vertically once, more passes spread the glow further
+    pub passes: u32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            intensity: 0.5,
+            passes: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VignetteSettings {
+    /// 0 leaves the corners alone, 1 makes them black
+    pub intensity: f32,
+    /// distance from the center where the darkening ends, 1 is the corners
+    pub radius: f32,
+    /// how far the darkening spreads inwards from `radius`
+    pub softness: f32,
+}
+
+impl Default for VignetteSettings {
+    fn default() -> Self {
+        Self {
+            intensity: 0.5,
+            radius: 1.0,
+            softness: 0.6,
+        }
+    }
+}
+
+/// a 3D lookup table that maps every color to a graded one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorGrading {
+    /// an opengl 3D texture, freed with `delete`
+    pub lut: GLuint,
+    /// width, height and depth of the lut
+    pub size: u32,
+    /// blends between the original (0) and the graded (1) colors
+    pub intensity: f32,
+}
+
+impl ColorGrading {
+    /// loads a lut laid out as a horizontal strip of `size` squares of `size`x`size`
+    /// pixels, red going right inside each square, green going down and blue
+    /// increasing from square to square. that's the usual format, e.g. 256x16 or 1024x32
+    pub fn load(path: &str) -> Option<Self> {
+        let img = match image::open(path) {
+            Ok(img) => img.to_rgba8(),
+            Err(err) => {
+                println!("Failed to load color grading lut {}: {}", path, err);
+                return None;
+            }
+        };
+
+        let size = img.height();
+        if img.width() != size * size {
+            println!("Failed to load color grading lut {}: expected a {}x{} strip", path, size * size, size);
+            return None;
+        }
+
+        let mut pixels = Vec::with_capacity((size * size * size * 4) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    pixels.extend_from_slice(&img.get_pixel(b * size + r, g).0);
+                }
+            }
+        }
+
+        let mut lut = 0;
+        unsafe {
+            GenTextures(1, &mut lut);
+            BindTexture(TEXTURE_3D, lut);
+            TexParameteri(TEXTURE_3D, TEXTURE_MIN_FILTER, LINEAR as GLint);
+            TexParameteri(TEXTURE_3D, TEXTURE_MAG_FILTER, LINEAR as GLint);
+            TexParameteri(TEXTURE_3D, TEXTURE_WRAP_S, CLAMP_TO_EDGE as GLint);
+            TexParameteri(TEXTURE_3D, TEXTURE_WRAP_T, CLAMP_TO_EDGE as GLint);
+            TexParameteri(TEXTURE_3D, TEXTURE_WRAP_R, CLAMP_TO_EDGE as GLint);
+            TexImage3D(
+                TEXTURE_3D,
+                0,
+                RGBA8 as GLint,
+                size as i32,
+                size as i32,
+                size as i32,
+                0,
+                RGBA,
+                UNSIGNED_BYTE,
+                pixels.as_ptr() as *const std::ffi::c_void,
+            );
+            BindTexture(TEXTURE_3D, 0);
+        }
+
+        Some(Self { lut, size, intensity: 1.0 })
+    }
+
+    pub unsafe fn delete(&self) {
+        DeleteTextures(1, &self.lut);
+    }
+}
+
+/// every pass is optional. the scene is drawn into a 16 bit float target, then
+/// bloom, exposure, tonemapping, gamma, color grading, vignette and fxaa are applied in that order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostProcessSettings {
+    pub exposure: f32,
+    pub tonemapping: Tonemapping,
+    /// 1 disables gamma correction
+    pub gamma: f32,
+    pub bloom: Option<BloomSettings>,
+    pub color_grading: Option<ColorGrading>,
+    pub vignette: Option<VignetteSettings>,
+    pub fxaa: bool,
+    /// multisampling of the hdr target, 0 disables it
+    pub samples: u32,
+}
+
+impl Default for PostProcessSettings {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            tonemapping: Tonemapping::Aces,
+            gamma: 2.2,
+            bloom: Some(BloomSettings::default()),
+            color_grading: None,
+            vignette: None,
+            fxaa: true,
+            samples: 0,
+        }
+    }
+}
+
+/// the intermediate targets, recreated whenever the screen size or sample count changes
+#[derive(Debug)]
+pub(crate) struct PostProcessTargets {
+    hdr: RenderTarget,
+    /* ping-ponged between while blurring, at half resolution */
+    bloom: [RenderTarget; 2],
+    /// the composited image fxaa reads from
+    ldr: RenderTarget,
+}
+
+impl PostProcessTargets {
+    fn new(width: u32, height: u32, samples: u32) -> Option<Self> {
+        let color = |width: u32, height: u32, hdr: bool| RenderTarget::new(RenderTargetSettings {
+            width,
+            height,
+            hdr,
+            depth_stencil: false,
+            ..Default::default()
+        });
+
+        let hdr = RenderTarget::new(RenderTargetSettings {
+            width,
+            height,
+            hdr: true,
+            samples,
+            ..Default::default()
+        })?;
+        let bloom = [color(width / 2, height / 2, true)?, color(width / 2, height / 2, true)?];
+        let ldr = color(width, height, false)?;
+
+        Some(Self { hdr, bloom, ldr })
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.hdr.resize(width, height);
+        self.ldr.resize(width, height);
+        for target in &mut self.bloom {
+            target.resize(width / 2, height / 2);
+        }
+    }
+}
+
+impl Renderer {
+    /// draws the scene into an hdr target and runs the post processing chain into
+    /// the currently bound framebuffer. the targets follow the size of the viewport,
+    /// which `EventLoop` keeps in sync with the window
+    pub(crate) unsafe fn draw_post_processed(&self, settings: &PostProcessSettings) {
+        let mut viewport = [0; 4];
+        GetIntegerv(VIEWPORT, viewport.as_mut_ptr());
+        let (width, height) = (viewport[2].max(1) as u32, viewport[3].max(1) as u32);
+
+        let mut targets = self.post_process_targets.borrow_mut();
+        let outdated = targets.as_ref().map_or(true, |t| t.hdr.settings.samples != settings.samples);
+        if outdated {
+            *targets = PostProcessTargets::new(width, height, settings.samples);
+        } else if let Some(t) = targets.as_mut() {
+            if t.hdr.settings.width != width || t.hdr.settings.height != height {
+                t.resize(width, height);
+            }
+        }
+
+        let Some(targets) = targets.as_ref() else {
+            println!("Failed to create the post processing targets, drawing without them");
+            self.draw_scene();
+            return;
+        };
+
+        targets.hdr.begin();
+        Clear(COLOR_BUFFER_BIT | DEPTH_BUFFER_BIT | STENCIL_BUFFER_BIT);
+        self.draw_scene();
+        targets.hdr.end();
+
+        let depth_test = IsEnabled(DEPTH_TEST) == TRUE;
+        let blend = IsEnabled(BLEND) == TRUE;
+        Disable(DEPTH_TEST);
+        Disable(BLEND);
+        // the fullscreen triangle needs a vao bound even though it has no attributes
+        BindVertexArray(self.empty_vao);
+
+        if let Some(bloom) = settings.bloom {
+            let texel = vec2(1.0 / targets.bloom[0].settings.width as f32, 1.0 / targets.bloom[0].settings.height as f32);
+
+            POST_BRIGHT_SHADER.use_shader();
+            POST_BRIGHT_SHADER.uniform_1f(cstr!("threshold"), bloom.threshold);
+            fullscreen_pass(&POST_BRIGHT_SHADER, &[targets.hdr.color_textures[0]], Some(&targets.bloom[0]));
+
+            POST_BLUR_SHADER.use_shader();
+            for _ in 0..bloom.passes {
+                POST_BLUR_SHADER.uniform_vec2f(cstr!("direction"), &(texel * Vec2::X));
+                fullscreen_pass(&POST_BLUR_SHADER, &[targets.bloom[0].color_textures[0]], Some(&targets.bloom[1]));
+                POST_BLUR_SHADER.uniform_vec2f(cstr!("direction"), &(texel * Vec2::Y));
+                fullscreen_pass(&POST_BLUR_SHADER, &[targets.bloom[1].color_textures[0]], Some(&targets.bloom[0]));
+            }
+        }
+
+        let shader = &POST_COMPOSITE_SHADER;
+        shader.use_shader();
+        shader.uniform_sampler(cstr!("scene"), 0);
+        shader.uniform_sampler(cstr!("bloom"), 1);
+        shader.uniform_sampler(cstr!("lut"), 2);
+        shader.uniform_1f(cstr!("exposure"), settings.exposure);
+        shader.uniform_1i(cstr!("tonemapping"), settings.tonemapping.id());
+        shader.uniform_1f(cstr!("gamma"), settings.gamma.max(0.0001));
+
+        shader.uniform_1i(cstr!("use_bloom"), settings.bloom.is_some() as i32);
+        shader.uniform_1f(cstr!("bloom_intensity"), settings.bloom.map_or(0.0, |b| b.intensity));
+
+        shader.uniform_1i(cstr!("use_lut"), settings.color_grading.is_some() as i32);
+        if let Some(grading) = settings.color_grading {
+            shader.uniform_1f(cstr!("lut_size"), grading.size as f32);
+            shader.uniform_1f(cstr!("lut_intensity"), grading.intensity);
+            ActiveTexture(TEXTURE2);
+            BindTexture(TEXTURE_3D, grading.lut);
+        }
+
+        shader.uniform_1i(cstr!("use_vignette"), settings.vignette.is_some() as i32);
+        if let Some(vignette) = settings.vignette {
+            shader.uniform_1f(cstr!("vignette_intensity"), vignette.intensity);
+            shader.uniform_1f(cstr!("vignette_radius"), vignette.radius);
+            shader.uniform_1f(cstr!("vignette_softness"), vignette.softness);
+        }
+
+        let composite_into = if settings.fxaa { Some(&targets.ldr) } else { None };
+        fullscreen_pass(shader, &[targets.hdr.color_textures[0], targets.bloom[0].color_textures[0]], composite_into);
+
+        if settings.fxaa {
+            POST_FXAA_SHADER.use_shader();
+            POST_FXAA_SHADER.uniform_vec2f(cstr!("texel"), &vec2(1.0 / width as f32, 1.0 / height as f32));
+            fullscreen_pass(&POST_FXAA_SHADER, &[targets.ldr.color_textures[0]], None);
+        }
+
+        ActiveTexture(TEXTURE2);
+        BindTexture(TEXTURE_3D, 0);
+        ActiveTexture(TEXTURE0);
+        BindVertexArray(0);
+        UseProgram(0);
+        if depth_test { Enable(DEPTH_TEST); }
+        if blend { Enable(BLEND); }
+    }
+}
+
+/// draws the fullscreen triangle with `shader` (already in use) reading `inputs` on
+/// units 0.., into `target` or the currently bound framebuffer if None
+unsafe fn fullscreen_pass(shader: &Shader, inputs: &[GLuint], target: Option<&RenderTarget>) {
+    if inputs.len() == 1 {
+        shader.uniform_sampler(cstr!("image"), 0);
+    }
+    for (unit, texture) in inputs.iter().enumerate() {
+        ActiveTexture(TEXTURE0 + unit as u32);
+        BindTexture(TEXTURE_2D, *texture);
+    }
+
+    if let Some(target) = target {
+        target.begin();
+    }
+    DrawArrays(TRIANGLES, 0, 3);
+    if let Some(target) = target {
+        target.end();
+    }
+}
@@ -0,0 +1,128 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::{ShaderError, MAX_LIGHTS, MAX_SHADOW_MAPS, SHADER_CHUNKS};
+
+thread_local! {
+    static CUSTOM_CHUNKS: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+}
+
+/// makes `code` available to every shader as #include "name". overrides the
+/// built in chunk with the same name, if any
+pub fn add_shader_chunk(name: &str, code: &str) {
+    CUSTOM_CHUNKS.with(|chunks| chunks.borrow_mut().insert(name.to_string(), code.to_string()));
+}
+
+/// the source of a chunk, custom ones first
+pub fn shader_chunk(name: &str) -> Option<String> {
+    CUSTOM_CHUNKS.with(|chunks| chunks.borrow().get(name).cloned())
+        .or_else(|| SHADER_CHUNKS.iter().find(|(n, _)| *n == name).map(|(_, code)| code.to_string()))
+}
+
+/// resolves every #include "name" against the shader chunks and puts the defines
+/// right after the #version line. each chunk is only pasted in once, later
+/// includes of the same chunk are dropped
+pub fn preprocess(code: &str, defines: &[(String, String)]) -> Result<String, ShaderError> {
+    let mut header = format!("#define LIGHT_BUFFER_SIZE {}\n#define MAX_SHADOW_MAPS {}\n", MAX_LIGHTS, MAX_SHADOW_MAPS);
+    for (name, value) in defines {
+        header += &format!("#define {} {}\n", name, value);
+    }
+
+    let mut body = String::new();
+    let mut included = vec![];
+    expand(code, &mut body, &mut included, &mut vec![])?;
+
+    // #version has to stay the very first thing in the source
+    match body.find("#version") {
+        Some(start) => {
+            let end = body[start..].find('\n').map_or(body.len(), |end| start + end + 1);
+            Ok(format!("{}{}{}", &body[..end], header, &body[end..]))
+        }
+        None => Ok(format!("{}{}", header, body)),
+    }
+}
+
+fn expand(code: &str, out: &mut String, included: &mut Vec<String>, stack: &mut Vec<String>) -> Result<(), ShaderError> {
+    for line in code.lines() {
+        let Some(rest) = line.trim_start().strip_prefix("#include") else {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        };
+
+        let name = rest.trim().trim_matches('"');
+        if stack.iter().any(|n| n == name) {
+            return Err(ShaderError::Preprocess { message: format!("\"{}\" includes itself through {:?}", name, stack) });
+        }
+        if included.iter().any(|n| n == name) {
+            continue;
+        }
+
+        let chunk = shader_chunk(name)
+            .ok_or_else(|| ShaderError::Preprocess { message: format!("no shader chunk named \"{}\"", name) })?;
+
+        included.push(name.to_string());
+        stack.push(name.to_string());
+        expand(&chunk, out, included, stack)?;
+        stack.pop();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn includes_are_pasted_in_once() {
+        add_shader_chunk("test_a.glsl", "float a;");
+        let code = preprocess("#include \"test_a.glsl\"\n#include \"test_a.glsl\"\nvoid main() {}", &[]).unwrap();
+
+        assert_eq!(code.matches("float a;").count(), 1);
+        assert!(!code.contains("#include"));
+        assert!(code.find("float a;").unwrap() < code.find("void main").unwrap());
+    }
+
+    #[test]
+    fn nested_includes_resolve() {
+        add_shader_chunk("test_inner.glsl", "float inner;");
+        add_shader_chunk("test_outer.glsl", "#include \"test_inner.glsl\"\nfloat outer;");
+        let code = preprocess("#include \"test_outer.glsl\"", &[]).unwrap();
+
+        assert!(code.find("float inner;").unwrap() < code.find("float outer;").unwrap());
+    }
+
+    #[test]
+    fn defines_go_right_after_version() {
+        let defines = [("HAS_TEST".to_string(), "1".to_string())];
+        let code = preprocess("#version 330 core\nvoid main() {}", &defines).unwrap();
+
+        let mut lines = code.lines();
+        assert_eq!(lines.next(), Some("#version 330 core"));
+        assert_eq!(lines.next(), Some(format!("#define LIGHT_BUFFER_SIZE {}", MAX_LIGHTS).as_str()));
+        assert_eq!(lines.next(), Some(format!("#define MAX_SHADOW_MAPS {}", MAX_SHADOW_MAPS).as_str()));
+        assert_eq!(lines.next(), Some("#define HAS_TEST 1"));
+    }
+
+    #[test]
+    fn defines_come_first_without_version() {
+        let code = preprocess("void main() {}", &[]).unwrap();
+        assert!(code.starts_with("#define LIGHT_BUFFER_SIZE"));
+    }
+
+    #[test]
+    fn missing_includes_fail() {
+        let result = preprocess("#include \"test_missing.glsl\"", &[]);
+        assert!(matches!(result, Err(ShaderError::Preprocess { .. })));
+    }
+
+    #[test]
+    fn include_cycles_fail() {
+        add_shader_chunk("test_ping.glsl", "#include \"test_pong.glsl\"");
+        add_shader_chunk("test_pong.glsl", "#include \"test_ping.glsl\"");
+        let result = preprocess("#include \"test_ping.glsl\"", &[]);
+
+        assert!(matches!(result, Err(ShaderError::Preprocess { .. })));
+    }
+}
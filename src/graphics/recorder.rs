@@ -0,0 +1,141 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use glfw::Key;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, ImageError};
+
+use crate::{EventLoop, Renderer};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordingFormat {
+    /// frame_00000.png, frame_00001.png, ... in a folder of their own
+    PngSequence,
+    /// a single looping gif. gifs only have 256 colors and centisecond frame delays
+    Gif,
+}
+
+enum Recording {
+    Png { directory: PathBuf, frame: u32 },
+    Gif { encoder: GifEncoder<File>, delay: Delay, path: PathBuf },
+}
+
+/// saves screenshots and records frames of the screen. call `update` once per
+/// frame after drawing, before `el.ui.draw()` to leave imgui out of the capture
+/// or after it to keep it in
+pub struct Recorder {
+    /// everything gets saved in here
+    pub directory: PathBuf,
+    /// takes a screenshot when pressed, None to disable
+    pub screenshot_key: Option<Key>,
+    recording: Option<Recording>,
+    /// what `EventLoop::fixed_dt` was before recording started
+    previous_fixed_dt: Option<f32>,
+}
+
+impl Recorder {
+    pub fn new(directory: impl AsRef<Path>) -> Self {
+        Self {
+            directory: directory.as_ref().to_path_buf(),
+            screenshot_key: Some(Key::F12),
+            recording: None,
+            previous_fixed_dt: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// takes the screenshot if the key was just pressed and stores a frame if recording.
+    /// a frame that fails to save stops the recording
+    pub unsafe fn update(&mut self, el: &mut EventLoop, renderer: &Renderer) {
+        if let Some(key) = self.screenshot_key {
+            if el.event_handler.key_just_pressed(key) {
+                match self.screenshot(renderer) {
+                    Ok(path) => println!("Saved screenshot to {}", path.display()),
+                    Err(err) => println!("Failed to save screenshot: {}", err),
+                }
+            }
+        }
+
+        let Some(recording) = self.recording.as_mut() else { return; };
+        let image = renderer.read_viewport();
+
+        let result = match recording {
+            Recording::Png { directory, frame } => {
+                let path = directory.join(format!("frame_{:05}.png", frame));
+                *frame += 1;
+                image.save(path)
+            }
+            Recording::Gif { encoder, delay, .. } => {
+                encoder.encode_frame(Frame::from_parts(image, 0, 0, *delay))
+            }
+        };
+
+        if let Err(err) = result {
+            println!("Failed to record frame, stopping: {}", err);
+            self.stop(el);
+        }
+    }
+
+    /// saves the current viewport as a png named after the time it was taken
+    pub unsafe fn screenshot(&self, renderer: &Renderer) -> Result<PathBuf, ImageError> {
+        std::fs::create_dir_all(&self.directory)?;
+        let path = self.directory.join(format!("screenshot_{}.png", timestamp()));
+
+        renderer.read_viewport().save(&path)?;
+        Ok(path)
+    }
+
+    /// starts recording at `fps` frames per second of game time. the event loop's
+    /// dt is fixed to 1 / fps until `stop`, so the recording plays back at normal
+    /// speed however slow capturing makes the game run
+    pub fn start(&mut self, el: &mut EventLoop, format: RecordingFormat, fps: u32) -> Result<(), ImageError> {
+        if self.is_recording() {
+            self.stop(el);
+        }
+
+        let fps = fps.max(1);
+        std::fs::create_dir_all(&self.directory)?;
+
+        self.recording = Some(match format {
+            RecordingFormat::PngSequence => {
+                let directory = self.directory.join(format!("recording_{}", timestamp()));
+                std::fs::create_dir_all(&directory)?;
+
+                Recording::Png { directory, frame: 0 }
+            }
+            RecordingFormat::Gif => {
+                let path = self.directory.join(format!("recording_{}.gif", timestamp()));
+                let mut encoder = GifEncoder::new(File::create(&path)?);
+                encoder.set_repeat(Repeat::Infinite)?;
+
+                Recording::Gif { encoder, delay: Delay::from_numer_denom_ms(1000, fps), path }
+            }
+        });
+
+        self.previous_fixed_dt = el.fixed_dt;
+        el.fixed_dt = Some(1.0 / fps as f32);
+
+        Ok(())
+    }
+
+    /// finishes the recording and gives the event loop its own dt back. returns
+    /// where the recording was saved
+    pub fn stop(&mut self, el: &mut EventLoop) -> Option<PathBuf> {
+        let recording = self.recording.take()?;
+        el.fixed_dt = self.previous_fixed_dt.take();
+
+        // the gif is finished when its encoder is dropped
+        match recording {
+            Recording::Png { directory, .. } => Some(directory),
+            Recording::Gif { path, .. } => Some(path),
+        }
+    }
+}
+
+fn timestamp() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_millis())
+}
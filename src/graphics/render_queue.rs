@@ -0,0 +1,255 @@
+use gl::*;
+use gl::types::{GLenum, GLint};
+use glam::Vec3;
+
+use crate::{DebugView, InstancedMesh, Mesh, Renderer, SkeletalMesh};
+
+/// when a mesh is drawn relative to the others. queues are drawn in this order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum RenderQueue {
+    /// front to back, so hidden pixels are rejected by the depth test early
+    #[default]
+    Opaque,
+    /// like opaque, for materials that discard pixels with an `alpha_cutoff`
+    AlphaTested,
+    /// back to front after the skybox, without writing depth
+    Transparent,
+    /// last, in the order they were added, over everything else
+    Overlay,
+}
+
+/// how a mesh's pixels combine with what's already drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// replaces what's behind it
+    Opaque,
+    /// mixes by the alpha of the color
+    Alpha,
+    /// the color is multiplied by its alpha beforehand
+    Premultiplied,
+    /// adds light, for fire and glows
+    Additive,
+    /// darkens what's behind it
+    Multiply,
+}
+
+impl RenderQueue {
+    /// what meshes in this queue use when they don't pick a blend mode
+    pub fn default_blend(&self) -> BlendMode {
+        match self {
+            RenderQueue::Opaque | RenderQueue::AlphaTested => BlendMode::Opaque,
+            RenderQueue::Transparent | RenderQueue::Overlay => BlendMode::Alpha,
+        }
+    }
+}
+
+impl BlendMode {
+    unsafe fn apply(&self) {
+        let (src, dst) = match self {
+            BlendMode::Opaque => {
+                Disable(BLEND);
+                return;
+            }
+            BlendMode::Alpha => (SRC_ALPHA, ONE_MINUS_SRC_ALPHA),
+            BlendMode::Premultiplied => (ONE, ONE_MINUS_SRC_ALPHA),
+            BlendMode::Additive => (SRC_ALPHA, ONE),
+            BlendMode::Multiply => (DST_COLOR, ZERO),
+        };
+
+        Enable(BLEND);
+        BlendFunc(src, dst);
+    }
+}
+
+/// whether blending was on and with which function, so a pass can change them
+/// and put back whatever the caller had
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct BlendState {
+    enabled: bool,
+    /// src rgb, dst rgb, src alpha, dst alpha
+    func: [GLint; 4],
+}
+
+impl BlendState {
+    pub(crate) unsafe fn current() -> Self {
+        let mut func = [0; 4];
+        for (value, name) in func.iter_mut().zip([BLEND_SRC_RGB, BLEND_DST_RGB, BLEND_SRC_ALPHA, BLEND_DST_ALPHA]) {
+            GetIntegerv(name, value);
+        }
+
+        Self { enabled: IsEnabled(BLEND) == TRUE, func }
+    }
+
+    pub(crate) unsafe fn restore(&self) {
+        if self.enabled { Enable(BLEND); } else { Disable(BLEND); }
+        BlendFuncSeparate(self.func[0] as GLenum, self.func[1] as GLenum, self.func[2] as GLenum, self.func[3] as GLenum);
+    }
+}
+
+enum DrawItem<'a> {
+    Mesh(&'a Mesh),
+    Instanced(&'a InstancedMesh),
+    Skeletal(&'a SkeletalMesh),
+}
+
+impl DrawItem<'_> {
+    unsafe fn draw(&self, renderer: &Renderer) {
+        match self {
+            DrawItem::Mesh(mesh) => mesh.draw(renderer),
+            DrawItem::Instanced(mesh) => mesh.draw(renderer),
+            DrawItem::Skeletal(mesh) => mesh.draw(renderer),
+        }
+    }
+}
+
+/// orders draws by queue first. opaque and alpha tested draws are grouped by
+/// shader and material to save state changes, then go front to back. transparent
+/// draws go back to front, overlays keep the order they came in. the sort is
+/// stable and the arenas iterate in a fixed order, so ties always land the same way
+fn sort_key(queue: RenderQueue, shader: u32, material: u32, distance: f32, order: usize) -> u64 {
+    let bits = (queue as u64) << 62;
+    // non negative floats sort the same as their bits
+    let depth = distance.max(0.0).to_bits() as u64;
+    let shader = (shader as u64) & 0xfff;
+    let material = (material as u64) & 0xfff;
+
+    match queue {
+        RenderQueue::Opaque | RenderQueue::AlphaTested => bits | shader << 50 | material << 38 | depth >> 2,
+        RenderQueue::Transparent => bits | (!depth & 0xffff_ffff) << 24 | shader << 12 | material,
+        RenderQueue::Overlay => bits | (order as u64 & ((1 << 62) - 1)),
+    }
+}
+
+impl Renderer {
+    /// draws every mesh, model, instanced and skeletal mesh through the render queues.
+    /// the skybox goes between the alpha tested and the transparent queues if `sky` is set
+    pub(crate) unsafe fn draw_meshes(&self, sky: bool) {
+        let mut items = vec![];
+        let mut add = |item, queue: RenderQueue, blend: Option<BlendMode>, material, default, position: Vec3| {
+            let material_handle = match material {
+                Some(handle) if self.materials.get(handle).is_some() => handle,
+                _ => default,
+            };
+            let shader = self.materials.get(material_handle).map_or(0, |material| material.shader.id);
+            let distance = position.distance(self.camera.pos);
+
+            let key = sort_key(queue, shader, material_handle.index, distance, items.len());
+            items.push((key, queue, blend.unwrap_or(queue.default_blend()), item));
+        };
+
+        for mesh in self.meshes.values() {
+            add(DrawItem::Mesh(mesh), mesh.queue, mesh.blend, mesh.material, self.default_material, mesh.world_position());
+        }
+        for mesh in self.instanced_meshes.values() {
+            // the instances are spread around, so the origin is as good as any
+            add(DrawItem::Instanced(mesh), mesh.queue, mesh.blend, mesh.material, self.default_instanced_material, Vec3::ZERO);
+        }
+        for model in self.models.values() {
+            for mesh in &model.meshes {
+                add(DrawItem::Mesh(mesh), mesh.queue, mesh.blend, mesh.material, self.default_material, mesh.world_position());
+            }
+        }
+        for mesh in self.skeletal_meshes.values() {
+            add(DrawItem::Skeletal(mesh), mesh.queue, mesh.blend, mesh.material, self.default_skeletal_material, mesh.world_position());
+        }
+
+        items.sort_by_key(|(key, ..)| *key);
+
+        // overdraw blends everything additively on its own
+        let blending = self.debug_view != DebugView::Overdraw;
+        let blend = BlendState::current();
+        let depth_test = IsEnabled(DEPTH_TEST) == TRUE;
+        let mut current: Option<(RenderQueue, BlendMode)> = None;
+        let mut sky_drawn = !sky;
+
+        for (_, queue, blend, item) in &items {
+            if !sky_drawn && *queue >= RenderQueue::Transparent {
+                self.draw_skybox();
+                sky_drawn = true;
+            }
+
+            if blending && current != Some((*queue, *blend)) {
+                blend.apply();
+                DepthMask(if *queue >= RenderQueue::Transparent { FALSE } else { TRUE });
+                if *queue == RenderQueue::Overlay { Disable(DEPTH_TEST); }
+                current = Some((*queue, *blend));
+            }
+
+            item.draw(self);
+        }
+
+        if !sky_drawn {
+            self.draw_skybox();
+        }
+
+        blend.restore();
+        DepthMask(TRUE);
+        if depth_test { Enable(DEPTH_TEST); } else { Disable(DEPTH_TEST); }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(keys: &[(usize, u64)]) -> Vec<usize> {
+        let mut keys = keys.to_vec();
+        keys.sort_by_key(|(_, key)| *key);
+        keys.into_iter().map(|(id, _)| id).collect()
+    }
+
+    #[test]
+    fn queues_are_drawn_in_order() {
+        let keys = [
+            (3, sort_key(RenderQueue::Overlay, 0, 0, 0.0, 0)),
+            (2, sort_key(RenderQueue::Transparent, 0, 0, 1000.0, 1)),
+            (1, sort_key(RenderQueue::AlphaTested, 4000, 4000, 1e9, 2)),
+            (0, sort_key(RenderQueue::Opaque, 4095, 4095, f32::MAX, 3)),
+        ];
+
+        assert_eq!(sorted(&keys), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn opaque_goes_front_to_back() {
+        let keys = [
+            (2, sort_key(RenderQueue::Opaque, 1, 1, 30.0, 0)),
+            (0, sort_key(RenderQueue::Opaque, 1, 1, 0.5, 1)),
+            (1, sort_key(RenderQueue::Opaque, 1, 1, 10.0, 2)),
+        ];
+
+        assert_eq!(sorted(&keys), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn opaque_groups_by_shader_before_distance() {
+        let keys = [
+            (1, sort_key(RenderQueue::Opaque, 2, 1, 1.0, 0)),
+            (0, sort_key(RenderQueue::Opaque, 1, 1, 50.0, 1)),
+        ];
+
+        assert_eq!(sorted(&keys), vec![0, 1]);
+    }
+
+    #[test]
+    fn transparent_goes_back_to_front() {
+        let keys = [
+            (1, sort_key(RenderQueue::Transparent, 1, 1, 10.0, 0)),
+            (2, sort_key(RenderQueue::Transparent, 1, 1, 0.5, 1)),
+            (0, sort_key(RenderQueue::Transparent, 1, 1, 30.0, 2)),
+        ];
+
+        assert_eq!(sorted(&keys), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn overlay_keeps_insertion_order() {
+        let keys = [
+            (0, sort_key(RenderQueue::Overlay, 9, 9, 100.0, 0)),
+            (1, sort_key(RenderQueue::Overlay, 1, 1, 1.0, 1)),
+            (2, sort_key(RenderQueue::Overlay, 5, 5, 50.0, 2)),
+        ];
+
+        assert_eq!(sorted(&keys), vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,292 @@
+use std::cell::Cell;
+
+use gl::*;
+use gl::types::{GLenum, GLint, GLuint};
+
+use crate::{Handle, Renderer, TextureHandle};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderTargetSettings {
+    pub width: u32,
+    pub height: u32,
+    /// number of color textures, written by `layout(location = n) out` in the fragment shader
+    pub color_attachments: u32,
+    /// stores colors as 16 bit floats instead of 8 bit normalized values
+    pub hdr: bool,
+    /// adds a 24 bit depth and 8 bit stencil texture
+    pub depth_stencil: bool,
+    /// more than 1 renders to multisampled buffers that `end` resolves into the textures
+    pub samples: u32,
+}
+
+impl Default for RenderTargetSettings {
+    fn default() -> Self {
+        Self {
+            width: 512,
+            height: 512,
+            color_attachments: 1,
+            hdr: false,
+            depth_stencil: true,
+            samples: 0,
+        }
+    }
+}
+
+/// an off-screen framebuffer. draw into it between `begin` and `end`, then use
+/// its textures like any other (see `Renderer::add_render_target`)
+#[derive(Debug)]
+pub struct RenderTarget {
+    pub settings: RenderTargetSettings,
+    pub fbo: GLuint,
+    pub color_textures: Vec<GLuint>,
+    /// 0 if the target has no depth
+    pub depth_texture: GLuint,
+
+    /* what actually gets drawn into when multisampling, 0 otherwise */
+    pub msaa_fbo: GLuint,
+    pub msaa_color_buffers: Vec<GLuint>,
+    pub msaa_depth_buffer: GLuint,
+
+    /// filled in by `Renderer::add_render_target`, one per color texture
+    pub texture_handles: Vec<TextureHandle>,
+
+    /* framebuffer and viewport to go back to in `end` */
+    previous: Cell<(GLint, [GLint; 4])>,
+}
+
+pub type RenderTargetHandle = Handle<RenderTarget>;
+
+impl RenderTarget {
+    pub fn new(settings: RenderTargetSettings) -> Option<Self> {
+        let mut target = Self {
+            settings,
+            fbo: 0,
+            color_textures: vec![],
+            depth_texture: 0,
+            msaa_fbo: 0,
+            msaa_color_buffers: vec![],
+            msaa_depth_buffer: 0,
+            texture_handles: vec![],
+            previous: Cell::new((0, [0; 4])),
+        };
+
+        unsafe {
+            GenFramebuffers(1, &mut target.fbo);
+            BindFramebuffer(FRAMEBUFFER, target.fbo);
+
+            for i in 0..settings.color_attachments {
+                let mut texture = 0;
+                GenTextures(1, &mut texture);
+                BindTexture(TEXTURE_2D, texture);
+                TexParameteri(TEXTURE_2D, TEXTURE_MIN_FILTER, LINEAR as GLint);
+                TexParameteri(TEXTURE_2D, TEXTURE_MAG_FILTER, LINEAR as GLint);
+                TexParameteri(TEXTURE_2D, TEXTURE_WRAP_S, CLAMP_TO_EDGE as GLint);
+                TexParameteri(TEXTURE_2D, TEXTURE_WRAP_T, CLAMP_TO_EDGE as GLint);
+                FramebufferTexture2D(FRAMEBUFFER, COLOR_ATTACHMENT0 + i, TEXTURE_2D, texture, 0);
+                target.color_textures.push(texture);
+            }
+
+            if settings.depth_stencil {
+                GenTextures(1, &mut target.depth_texture);
+                BindTexture(TEXTURE_2D, target.depth_texture);
+                TexParameteri(TEXTURE_2D, TEXTURE_MIN_FILTER, NEAREST as GLint);
+                TexParameteri(TEXTURE_2D, TEXTURE_MAG_FILTER, NEAREST as GLint);
+                TexParameteri(TEXTURE_2D, TEXTURE_WRAP_S, CLAMP_TO_EDGE as GLint);
+                TexParameteri(TEXTURE_2D, TEXTURE_WRAP_T, CLAMP_TO_EDGE as GLint);
+                FramebufferTexture2D(FRAMEBUFFER, DEPTH_STENCIL_ATTACHMENT, TEXTURE_2D, target.depth_texture, 0);
+            }
+            BindTexture(TEXTURE_2D, 0);
+
+            if settings.samples > 1 {
+                GenFramebuffers(1, &mut target.msaa_fbo);
+                BindFramebuffer(FRAMEBUFFER, target.msaa_fbo);
+
+                for i in 0..settings.color_attachments {
+                    let mut buffer = 0;
+                    GenRenderbuffers(1, &mut buffer);
+                    BindRenderbuffer(RENDERBUFFER, buffer);
+                    FramebufferRenderbuffer(FRAMEBUFFER, COLOR_ATTACHMENT0 + i, RENDERBUFFER, buffer);
+                    target.msaa_color_buffers.push(buffer);
+                }
+
+                if settings.depth_stencil {
+                    GenRenderbuffers(1, &mut target.msaa_depth_buffer);
+                    BindRenderbuffer(RENDERBUFFER, target.msaa_depth_buffer);
+                    FramebufferRenderbuffer(FRAMEBUFFER, DEPTH_STENCIL_ATTACHMENT, RENDERBUFFER, target.msaa_depth_buffer);
+                }
+                BindRenderbuffer(RENDERBUFFER, 0);
+            }
+
+            BindFramebuffer(FRAMEBUFFER, 0);
+        }
+
+        target.resize(settings.width, settings.height);
+
+        let complete = unsafe { target.is_complete() };
+        if complete {
+            Some(target)
+        } else {
+            println!("Failed to create render target, the framebuffer is incomplete");
+            None
+        }
+    }
+
+    /// reallocates every attachment, the texture ids stay the same so handles remain valid
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.settings.width = width.max(1);
+        self.settings.height = height.max(1);
+        let (w, h) = (self.settings.width as i32, self.settings.height as i32);
+        let (internal, ty) = self.color_format();
+
+        unsafe {
+            for texture in &self.color_textures {
+                BindTexture(TEXTURE_2D, *texture);
+                TexImage2D(TEXTURE_2D, 0, internal as GLint, w, h, 0, RGBA, ty, std::ptr::null());
+            }
+            if self.depth_texture != 0 {
+                BindTexture(TEXTURE_2D, self.depth_texture);
+                TexImage2D(TEXTURE_2D, 0, DEPTH24_STENCIL8 as GLint, w, h, 0, DEPTH_STENCIL, UNSIGNED_INT_24_8, std::ptr::null());
+            }
+            BindTexture(TEXTURE_2D, 0);
+
+            let samples = self.settings.samples as i32;
+            for buffer in &self.msaa_color_buffers {
+                BindRenderbuffer(RENDERBUFFER, *buffer);
+                RenderbufferStorageMultisample(RENDERBUFFER, samples, internal, w, h);
+            }
+            if self.msaa_depth_buffer != 0 {
+                BindRenderbuffer(RENDERBUFFER, self.msaa_depth_buffer);
+                RenderbufferStorageMultisample(RENDERBUFFER, samples, DEPTH24_STENCIL8, w, h);
+            }
+            BindRenderbuffer(RENDERBUFFER, 0);
+        }
+    }
+
+    fn color_format(&self) -> (GLenum, GLenum) {
+        if self.settings.hdr {
+            (RGBA16F, FLOAT)
+        } else {
+            (RGBA8, UNSIGNED_BYTE)
+        }
+    }
+
+    pub unsafe fn is_complete(&self) -> bool {
+        let mut complete = true;
+
+        for fbo in [self.fbo, self.msaa_fbo] {
+            if fbo == 0 { continue; }
+            BindFramebuffer(FRAMEBUFFER, fbo);
+            complete &= CheckFramebufferStatus(FRAMEBUFFER) == FRAMEBUFFER_COMPLETE;
+        }
+        BindFramebuffer(FRAMEBUFFER, 0);
+
+        complete
+    }
+
+    /// redirects drawing into this target until `end` is called
+    pub unsafe fn begin(&self) {
+        let mut fbo = 0;
+        GetIntegerv(DRAW_FRAMEBUFFER_BINDING, &mut fbo);
+        let mut viewport = [0; 4];
+        GetIntegerv(VIEWPORT, viewport.as_mut_ptr());
+        self.previous.set((fbo, viewport));
+
+        BindFramebuffer(FRAMEBUFFER, if self.msaa_fbo != 0 { self.msaa_fbo } else { self.fbo });
+        let buffers = (0..self.settings.color_attachments).map(|i| COLOR_ATTACHMENT0 + i).collect::<Vec<_>>();
+        DrawBuffers(buffers.len() as i32, buffers.as_ptr());
+        Viewport(0, 0, self.settings.width as i32, self.settings.height as i32);
+    }
+
+    /// resolves the multisampled buffers, if any, and goes back to the previous framebuffer
+    pub unsafe fn end(&self) {
+        if self.msaa_fbo != 0 {
+            let (w, h) = (self.settings.width as i32, self.settings.height as i32);
+            BindFramebuffer(READ_FRAMEBUFFER, self.msaa_fbo);
+            BindFramebuffer(DRAW_FRAMEBUFFER, self.fbo);
+
+            for i in 0..self.settings.color_attachments {
+                ReadBuffer(COLOR_ATTACHMENT0 + i);
+                DrawBuffer(COLOR_ATTACHMENT0 + i);
+                BlitFramebuffer(0, 0, w, h, 0, 0, w, h, COLOR_BUFFER_BIT, LINEAR);
+            }
+            if self.depth_texture != 0 {
+                BlitFramebuffer(0, 0, w, h, 0, 0, w, h, DEPTH_BUFFER_BIT | STENCIL_BUFFER_BIT, NEAREST);
+            }
+        }
+
+        let (fbo, viewport) = self.previous.get();
+        BindFramebuffer(FRAMEBUFFER, fbo as GLuint);
+        Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+    }
+
+    pub fn color_texture(&self, index: usize) -> Option<GLuint> {
+        self.color_textures.get(index).copied()
+    }
+
+    /// for `ui.image`. opengl textures start at the bottom, so flip the uvs:
+    /// `.uv0([0.0, 1.0]).uv1([1.0, 0.0])`
+    pub fn imgui_texture(&self, index: usize) -> Option<imgui::TextureId> {
+        self.color_texture(index).map(|id| imgui::TextureId::new(id as usize))
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        unsafe {
+            DeleteFramebuffers(1, &self.fbo);
+            DeleteTextures(self.color_textures.len() as i32, self.color_textures.as_ptr());
+            if self.depth_texture != 0 {
+                DeleteTextures(1, &self.depth_texture);
+            }
+
+            if self.msaa_fbo != 0 {
+                DeleteFramebuffers(1, &self.msaa_fbo);
+                DeleteRenderbuffers(self.msaa_color_buffers.len() as i32, self.msaa_color_buffers.as_ptr());
+                if self.msaa_depth_buffer != 0 {
+                    DeleteRenderbuffers(1, &self.msaa_depth_buffer);
+                }
+            }
+        }
+    }
+}
+
+impl Renderer {
+    /// registers each color texture of the target as a texture, see `texture_handles`
+    pub fn add_render_target(&mut self, mut target: RenderTarget) -> Option<RenderTargetHandle> {
+        target.texture_handles = target.color_textures.iter()
+            .map(|texture| self.textures.insert(*texture))
+            .collect();
+
+        Some(self.render_targets.insert(target))
+    }
+
+    pub fn destroy_render_target(&mut self, handle: RenderTargetHandle) {
+        if let Some(target) = self.render_targets.remove(handle) {
+            // the target deletes the textures itself
+            for texture in &target.texture_handles {
+                self.textures.remove(*texture);
+            }
+        } else {
+            println!("Failed to remove render target");
+        }
+    }
+
+    /// the texture handle of a color attachment, usable with `Material::set_texture`
+    pub fn render_target_texture(&self, handle: RenderTargetHandle, index: usize) -> Option<TextureHandle> {
+        self.render_targets.get(handle)?.texture_handles.get(index).copied()
+    }
+
+    /// clears the target and draws the whole scene into it, without post processing.
+    /// hide the meshes that show this target first, a texture can't be sampled while it's drawn to
+    pub unsafe fn draw_to(&self, handle: RenderTargetHandle) {
+        let Some(target) = self.render_targets.get(handle) else {
+            println!("Failed to draw to render target");
+            return;
+        };
+
+        target.begin();
+        Clear(COLOR_BUFFER_BIT | DEPTH_BUFFER_BIT | STENCIL_BUFFER_BIT);
+        self.draw_scene();
+        target.end();
+    }
+}
@@ -1,33 +1,119 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 
 use gl::types::GLuint;
+use glam::Vec3;
 
-use crate::{Camera, Light, LightHandle, Mesh, MeshHandle, Model, ModelHandle, SkeletalMesh, SkeletalMeshHandle, TextureHandle};
+use crate::{Arena, AtmosphereSettings, Camera, CameraBlock, Cubemap, CubemapHandle, DebugDraw, DebugView, Font, InstancedMesh, Light, LightHandle, LightsBlock, Material, MaterialHandle, MaterialParam, Mesh, Model, Node, PostProcessSettings, PostProcessTargets, RenderTarget, ShadowMap, SkeletalMesh, Text, TextBuffers, UniformBuffer, CAMERA_BLOCK_BINDING, DEFAULT_INSTANCED_SHADER, LIGHTS_BLOCK_BINDING, RUSSIMP_SHADER};
 
 pub struct Renderer {
-    pub meshes: HashMap<MeshHandle, Mesh>,
-    pub lights: HashMap<LightHandle, Light>,
-    pub textures: HashMap<TextureHandle, GLuint>,
-    pub models: HashMap<ModelHandle, Model>,
-    pub skeletal_meshes: HashMap<SkeletalMeshHandle, SkeletalMesh>,
+    pub meshes: Arena<Mesh>,
+    pub instanced_meshes: Arena<InstancedMesh>,
+    pub lights: Arena<Light>,
+    pub textures: Arena<GLuint>,
+    pub models: Arena<Model>,
+    pub skeletal_meshes: Arena<SkeletalMesh>,
+    pub materials: Arena<Material>,
+    pub render_targets: Arena<RenderTarget>,
+    pub cubemaps: Arena<Cubemap>,
+    pub fonts: Arena<Font>,
+    pub texts: Arena<Text>,
+    /// the scene graph, moves the meshes, lights and camera its nodes own
+    pub nodes: Arena<Node>,
+
+    /// drawn behind everything and reflected by materials with a reflectivity
+    pub skybox: Option<CubemapHandle>,
+    /// a procedural sky drawn instead of the skybox
+    pub atmosphere: Option<AtmosphereSettings>,
+    /// the atmosphere rendered for reflections, with the settings it was rendered with
+    pub(crate) atmosphere_environment: Option<(Cubemap, AtmosphereSettings)>,
+
+    /// None draws straight to the screen
+    pub post_process: Option<PostProcessSettings>,
+    pub(crate) post_process_targets: RefCell<Option<PostProcessTargets>>,
     pub camera: Camera,
+    /// light that reaches every surface regardless of the lights in the scene
+    pub ambient: Vec3,
+
+    /// one per light with shadows enabled, kept in sync by `update`
+    pub shadow_maps: HashMap<LightHandle, ShadowMap>,
+
+    /* what each kind of mesh is drawn with if it has no material of its own */
+    pub default_material: MaterialHandle,
+    pub default_instanced_material: MaterialHandle,
+    pub default_skeletal_material: MaterialHandle,
+
+    /* shared by every shader, refilled once per frame in `draw` */
+    pub camera_ubo: UniformBuffer,
+    pub lights_ubo: UniformBuffer,
+
+    /// lines drawn for a single frame, see `DebugDraw`
+    pub debug: DebugDraw,
+    pub debug_view: DebugView,
+    /// for draws that build their vertices from gl_VertexID
+    pub(crate) empty_vao: GLuint,
+    pub(crate) text_buffers: TextBuffers,
 } 
 
 impl Renderer {
     pub fn new() -> Self {
         let camera = Camera::new();
 
+        let mut materials = Arena::new();
+        let default_material = materials.insert(Material::default());
+        // instances carry their own color
+        let mut instanced_material = Material::new(*DEFAULT_INSTANCED_SHADER);
+        instanced_material.set_param("has_texture", MaterialParam::Int(0));
+        instanced_material.set_reflectivity(0.0);
+        instanced_material.set_alpha_cutoff(0.0);
+        let default_instanced_material = materials.insert(instanced_material);
+        let default_skeletal_material = materials.insert(Material {
+            shader: *RUSSIMP_SHADER,
+            ..Default::default()
+        });
+
+        let mut empty_vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut empty_vao);
+        }
+
         Self {
             camera,
-            meshes: HashMap::new(),
-            lights: HashMap::new(),
-            textures: HashMap::new(),
-            models: HashMap::new(),
-            skeletal_meshes: HashMap::new(),
+            ambient: Vec3::splat(0.1),
+            materials,
+            shadow_maps: HashMap::new(),
+            default_material,
+            default_instanced_material,
+            default_skeletal_material,
+            camera_ubo: UniformBuffer::new(CAMERA_BLOCK_BINDING, std::mem::size_of::<CameraBlock>()),
+            lights_ubo: UniformBuffer::new(LIGHTS_BLOCK_BINDING, std::mem::size_of::<LightsBlock>()),
+            meshes: Arena::new(),
+            instanced_meshes: Arena::new(),
+            lights: Arena::new(),
+            textures: Arena::new(),
+            models: Arena::new(),
+            skeletal_meshes: Arena::new(),
+            render_targets: Arena::new(),
+            cubemaps: Arena::new(),
+            fonts: Arena::new(),
+            texts: Arena::new(),
+            nodes: Arena::new(),
+            skybox: None,
+            atmosphere: None,
+            atmosphere_environment: None,
+            empty_vao,
+            text_buffers: TextBuffers::new(),
+            debug: DebugDraw::new(),
+            debug_view: DebugView::Lit,
+            post_process: None,
+            post_process_targets: RefCell::new(None),
         }
     }
 
     pub fn update(&mut self) {
+        self.debug.clear();
+        self.update_scene();
+
         for mesh in self.meshes.values_mut() {
             if mesh.has_been_set_up == false {
                 mesh.setup_mesh();
@@ -35,27 +121,58 @@ impl Renderer {
             }
         }
 
+        for model in self.models.values_mut() {
+            for mesh in model.meshes.iter_mut() {
+                if mesh.has_been_set_up == false {
+                    mesh.setup_mesh();
+                    mesh.has_been_set_up = true;
+                }
+            }
+        }
+
+        for mesh in self.instanced_meshes.values_mut() {
+            if mesh.has_been_set_up == false {
+                mesh.setup_mesh();
+                mesh.has_been_set_up = true;
+            } else if mesh.needs_upload {
+                mesh.upload_instances();
+            }
+        }
+
         for mesh in self.skeletal_meshes.values_mut() {
             if mesh.has_been_set_up == false {
                 mesh.setup_mesh();
                 mesh.has_been_set_up = true;
             }
         }
+
+        self.update_atmosphere();
+        self.update_texts();
+        self.update_shadow_maps();
+        self.reload_shaders();
     }
 
+    /// draws everything, through the post processing chain if it's enabled
     pub unsafe fn draw(&self) {
-        // regular meshes
-        for mesh in self.meshes.values() {
-            mesh.draw(&self);
-        }
-        
-        for model in self.models.values() {
-            model.draw(&self);
-        }
-        
-        // skeletal meshes
-        for mesh in self.skeletal_meshes.values() {
-            mesh.draw(&self);
+        match self.post_process {
+            Some(settings) => self.draw_post_processed(&settings),
+            None => self.draw_scene(),
         }
+
+        // screen space text goes on top, untouched by post processing
+        self.draw_texts(true);
+    }
+
+    /// draws everything straight into the currently bound framebuffer
+    pub unsafe fn draw_scene(&self) {
+        self.render_shadow_maps();
+        self.upload_uniform_buffers();
+        self.bind_environment();
+
+        // the skybox is drawn in between the queues
+        self.draw_meshes_with_debug_view();
+
+        self.draw_texts(false);
+        self.debug.draw();
     }
 }
\ No newline at end of file
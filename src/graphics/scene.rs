@@ -0,0 +1,423 @@
+use glam::{Mat4, Quat, Vec3};
+
+use crate::{Arena, Handle, LightHandle, LightKind, MeshHandle, ModelHandle, Renderer, SkeletalMeshHandle};
+
+/// what a node moves around. the owned thing ends up wherever the node is in the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NodeContent {
+    /// only groups its children
+    #[default]
+    Empty,
+    Mesh(MeshHandle),
+    Model(ModelHandle),
+    SkeletalMesh(SkeletalMeshHandle),
+    /// points and spot lights are moved, spot and directional lights are also turned to face -Z
+    Light(LightHandle),
+    /// the renderer's camera, looking down -Z
+    Camera,
+}
+
+/// a transform in the scene graph, relative to its parent. the transform is only
+/// changed through the setters so the node knows when its world matrix has to be
+/// recomputed, which happens for it and everything below it in `Renderer::update`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub name: String,
+
+    position: Vec3,
+    rotation: Quat,
+    scale: Vec3,
+
+    parent: Option<NodeHandle>,
+    children: Vec<NodeHandle>,
+    content: NodeContent,
+
+    /// local transform on top of the parent's world matrix, as of the last update
+    world: Mat4,
+    dirty: bool,
+}
+
+pub type NodeHandle = Handle<Node>;
+
+impl Node {
+    pub fn new(name: &str, content: NodeContent) -> Self {
+        Self {
+            name: name.to_string(),
+            position: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+            parent: None,
+            children: vec![],
+            content,
+            world: Mat4::IDENTITY,
+            dirty: true,
+        }
+    }
+
+    /// a node with nothing attached, for grouping others
+    pub fn empty(name: &str) -> Self {
+        Self::new(name, NodeContent::Empty)
+    }
+
+    pub fn with_transform(mut self, position: Vec3, rotation: Quat, scale: Vec3) -> Self {
+        self.position = position;
+        self.rotation = rotation;
+        self.scale = scale;
+        self
+    }
+
+    pub fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    pub fn rotation(&self) -> Quat {
+        self.rotation
+    }
+
+    pub fn scale(&self) -> Vec3 {
+        self.scale
+    }
+
+    pub fn set_position(&mut self, position: Vec3) {
+        self.position = position;
+        self.dirty = true;
+    }
+
+    pub fn set_rotation(&mut self, rotation: Quat) {
+        self.rotation = rotation;
+        self.dirty = true;
+    }
+
+    pub fn set_scale(&mut self, scale: Vec3) {
+        self.scale = scale;
+        self.dirty = true;
+    }
+
+    pub fn translate(&mut self, offset: Vec3) {
+        self.set_position(self.position + offset);
+    }
+
+    /// applied after the current rotation, in the parent's space
+    pub fn rotate(&mut self, rotation: Quat) {
+        self.set_rotation((rotation * self.rotation).normalize());
+    }
+
+    pub fn content(&self) -> NodeContent {
+        self.content
+    }
+
+    /// whatever was attached before keeps the last transform it was given
+    pub fn set_content(&mut self, content: NodeContent) {
+        self.content = content;
+        self.dirty = true;
+    }
+
+    pub fn parent(&self) -> Option<NodeHandle> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeHandle] {
+        &self.children
+    }
+
+    pub fn local_matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.position)
+    }
+
+    /// as of the last `Renderer::update`
+    pub fn world_matrix(&self) -> Mat4 {
+        self.world
+    }
+
+    pub fn world_position(&self) -> Vec3 {
+        self.world.w_axis.truncate()
+    }
+}
+
+impl Renderer {
+    /// adds a node at the root of the scene
+    pub fn add_node(&mut self, mut node: Node) -> Option<NodeHandle> {
+        node.parent = None;
+        node.children.clear();
+        node.dirty = true;
+
+        Some(self.nodes.insert(node))
+    }
+
+    /// adds a node below `parent`, its transform is relative to the parent's
+    pub fn add_child_node(&mut self, parent: NodeHandle, node: Node) -> Option<NodeHandle> {
+        insert_child(&mut self.nodes, parent, node)
+    }
+
+    /// removes the node along with everything below it. what the nodes own is left as is
+    pub fn destroy_node(&mut self, handle: NodeHandle) {
+        if !remove_subtree(&mut self.nodes, handle) {
+            println!("Failed to remove node, or there was no node to remove");
+        }
+    }
+
+    /// moves the node under another one, or to the root with None, without moving
+    /// it in the world. fails if the new parent is the node itself or below it
+    pub fn set_parent(&mut self, handle: NodeHandle, parent: Option<NodeHandle>) -> bool {
+        reparent(&mut self.nodes, handle, parent)
+    }
+
+    /// walks up to the root, so it's correct even before the next update
+    pub fn compute_world_matrix(&self, handle: NodeHandle) -> Mat4 {
+        world_matrix(&self.nodes, handle)
+    }
+
+    /// recomputes the world matrices of dirty nodes and everything below them,
+    /// then moves whatever those nodes own
+    pub(crate) fn update_scene(&mut self) {
+        for (content, world) in update_world_matrices(&mut self.nodes) {
+            self.apply_node_transform(content, world);
+        }
+    }
+
+    fn apply_node_transform(&mut self, content: NodeContent, world: Mat4) {
+        let (_, rotation, position) = world.to_scale_rotation_translation();
+        let forward = (rotation * Vec3::NEG_Z).normalize_or_zero();
+
+        match content {
+            NodeContent::Empty => {}
+            NodeContent::Mesh(handle) => {
+                if let Some(mesh) = self.meshes.get_mut(handle) {
+                    mesh.node_transform = world;
+                }
+            }
+            NodeContent::Model(handle) => {
+                if let Some(model) = self.models.get_mut(handle) {
+                    for mesh in &mut model.meshes {
+                        mesh.node_transform = world;
+                    }
+                }
+            }
+            NodeContent::SkeletalMesh(handle) => {
+                if let Some(mesh) = self.skeletal_meshes.get_mut(handle) {
+                    mesh.node_transform = world;
+                }
+            }
+            NodeContent::Light(handle) => {
+                if let Some(light) = self.lights.get_mut(handle) {
+                    light.position = position;
+                    match &mut light.kind {
+                        LightKind::Directional { direction } | LightKind::Spot { direction, .. } => *direction = forward,
+                        LightKind::Point => {}
+                    }
+                }
+            }
+            NodeContent::Camera => {
+                let camera = &mut self.camera;
+                camera.pos = position;
+                camera.front = forward;
+                camera.up = (rotation * Vec3::Y).normalize_or_zero();
+                camera.right = forward.cross(camera.up).normalize_or_zero();
+                // so mouse look carries on from here instead of snapping back
+                camera.pitch = forward.y.clamp(-1.0, 1.0).asin().to_degrees();
+                camera.yaw = forward.z.atan2(forward.x).to_degrees();
+                camera.view = Mat4::look_at_rh(camera.pos, camera.pos + camera.front, camera.up);
+            }
+        }
+    }
+}
+
+fn insert_child(nodes: &mut Arena<Node>, parent: NodeHandle, mut node: Node) -> Option<NodeHandle> {
+    if !nodes.contains(parent) {
+        println!("Failed to add child node, the parent does not exist");
+        return None;
+    }
+
+    node.parent = Some(parent);
+    node.children.clear();
+    node.dirty = true;
+
+    let handle = nodes.insert(node);
+    nodes[parent].children.push(handle);
+
+    Some(handle)
+}
+
+/// false if the node didn't exist
+fn remove_subtree(nodes: &mut Arena<Node>, handle: NodeHandle) -> bool {
+    let Some(node) = nodes.remove(handle) else {
+        return false;
+    };
+
+    if let Some(parent) = node.parent.and_then(|parent| nodes.get_mut(parent)) {
+        parent.children.retain(|child| *child != handle);
+    }
+
+    let mut stack = node.children;
+    while let Some(child) = stack.pop() {
+        if let Some(child) = nodes.remove(child) {
+            stack.extend(child.children);
+        }
+    }
+
+    true
+}
+
+fn reparent(nodes: &mut Arena<Node>, handle: NodeHandle, parent: Option<NodeHandle>) -> bool {
+    if !nodes.contains(handle) {
+        println!("Failed to set parent, the node does not exist");
+        return false;
+    }
+
+    if let Some(parent) = parent {
+        if !nodes.contains(parent) {
+            println!("Failed to set parent, the parent does not exist");
+            return false;
+        }
+
+        let mut ancestor = Some(parent);
+        while let Some(current) = ancestor {
+            if current == handle {
+                println!("Failed to set parent, a node can't be moved below itself");
+                return false;
+            }
+            ancestor = nodes[current].parent;
+        }
+    }
+
+    let world = world_matrix(nodes, handle);
+    let parent_world = parent.map_or(Mat4::IDENTITY, |parent| world_matrix(nodes, parent));
+    let (scale, rotation, position) = (parent_world.inverse() * world).to_scale_rotation_translation();
+
+    if let Some(old_parent) = nodes[handle].parent.and_then(|old_parent| nodes.get_mut(old_parent)) {
+        old_parent.children.retain(|child| *child != handle);
+    }
+    if let Some(parent) = parent {
+        nodes[parent].children.push(handle);
+    }
+
+    let node = &mut nodes[handle];
+    node.parent = parent;
+    node.position = position;
+    node.rotation = rotation.normalize();
+    node.scale = scale;
+    node.dirty = true;
+
+    true
+}
+
+fn world_matrix(nodes: &Arena<Node>, handle: NodeHandle) -> Mat4 {
+    let mut matrix = Mat4::IDENTITY;
+    let mut current = Some(handle);
+
+    while let Some(node) = current.and_then(|handle| nodes.get(handle)) {
+        matrix = node.local_matrix() * matrix;
+        current = node.parent;
+    }
+
+    matrix
+}
+
+/// the content and new world matrix of every node that moved
+fn update_world_matrices(nodes: &mut Arena<Node>) -> Vec<(NodeContent, Mat4)> {
+    let mut moved = vec![];
+
+    // parents are always visited before their children
+    let mut stack: Vec<(NodeHandle, Mat4, bool)> = nodes.iter()
+        .filter(|(_, node)| node.parent.is_none())
+        .map(|(handle, _)| (handle, Mat4::IDENTITY, false))
+        .collect();
+    stack.reverse();
+
+    while let Some((handle, parent_world, parent_changed)) = stack.pop() {
+        let Some(node) = nodes.get_mut(handle) else { continue };
+
+        let changed = parent_changed || node.dirty;
+        if changed {
+            node.world = parent_world * node.local_matrix();
+            node.dirty = false;
+            moved.push((node.content, node.world));
+        }
+
+        let world = node.world;
+        stack.extend(node.children.iter().rev().map(|child| (*child, world, changed)));
+    }
+
+    moved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root(nodes: &mut Arena<Node>, position: Vec3) -> NodeHandle {
+        nodes.insert(Node::empty("root").with_transform(position, Quat::IDENTITY, Vec3::ONE))
+    }
+
+    #[test]
+    fn set_parent_keeps_the_world_transform() {
+        let mut nodes = Arena::new();
+        let a = root(&mut nodes, Vec3::new(1.0, 2.0, 3.0));
+        let b = nodes.insert(
+            Node::empty("b").with_transform(Vec3::new(-4.0, 0.0, 2.0), Quat::from_rotation_y(1.0), Vec3::splat(2.0))
+        );
+        let child = insert_child(&mut nodes, b, Node::empty("child").with_transform(Vec3::X, Quat::from_rotation_x(0.5), Vec3::ONE)).unwrap();
+
+        let before = world_matrix(&nodes, child);
+        assert!(reparent(&mut nodes, child, Some(a)));
+
+        assert_eq!(nodes[child].parent(), Some(a));
+        assert_eq!(nodes[a].children(), &[child]);
+        assert!(nodes[b].children().is_empty());
+        assert!(world_matrix(&nodes, child).abs_diff_eq(before, 1e-4));
+
+        assert!(reparent(&mut nodes, child, None));
+        assert!(world_matrix(&nodes, child).abs_diff_eq(before, 1e-4));
+    }
+
+    #[test]
+    fn set_parent_rejects_cycles() {
+        let mut nodes = Arena::new();
+        let a = root(&mut nodes, Vec3::ZERO);
+        let b = insert_child(&mut nodes, a, Node::empty("b")).unwrap();
+
+        assert!(!reparent(&mut nodes, a, Some(b)));
+        assert!(!reparent(&mut nodes, a, Some(a)));
+        assert_eq!(nodes[a].parent(), None);
+    }
+
+    #[test]
+    fn dirty_nodes_move_their_descendants() {
+        let mut nodes = Arena::new();
+        let a = root(&mut nodes, Vec3::ZERO);
+        let b = insert_child(&mut nodes, a, Node::empty("b").with_transform(Vec3::X, Quat::IDENTITY, Vec3::ONE)).unwrap();
+        let c = insert_child(&mut nodes, b, Node::empty("c").with_transform(Vec3::Y, Quat::IDENTITY, Vec3::ONE)).unwrap();
+        let other = root(&mut nodes, Vec3::Z);
+
+        assert_eq!(update_world_matrices(&mut nodes).len(), 4);
+        // nothing changed since
+        assert!(update_world_matrices(&mut nodes).is_empty());
+
+        nodes[a].set_position(Vec3::new(0.0, 0.0, -5.0));
+        let moved = update_world_matrices(&mut nodes);
+
+        assert_eq!(moved.len(), 3);
+        assert_eq!(nodes[b].world_position(), Vec3::new(1.0, 0.0, -5.0));
+        assert_eq!(nodes[c].world_position(), Vec3::new(1.0, 1.0, -5.0));
+        assert_eq!(nodes[other].world_position(), Vec3::Z);
+    }
+
+    #[test]
+    fn removing_a_node_removes_its_subtree() {
+        let mut nodes = Arena::new();
+        let a = root(&mut nodes, Vec3::ZERO);
+        let b = insert_child(&mut nodes, a, Node::empty("b")).unwrap();
+        let c = insert_child(&mut nodes, b, Node::empty("c")).unwrap();
+        let d = insert_child(&mut nodes, c, Node::empty("d")).unwrap();
+        let sibling = insert_child(&mut nodes, a, Node::empty("sibling")).unwrap();
+
+        assert!(remove_subtree(&mut nodes, b));
+
+        assert!(!nodes.contains(b));
+        assert!(!nodes.contains(c));
+        assert!(!nodes.contains(d));
+        assert!(nodes.contains(sibling));
+        assert_eq!(nodes[a].children(), &[sibling]);
+        assert!(!remove_subtree(&mut nodes, b));
+    }
+}
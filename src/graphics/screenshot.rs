@@ -0,0 +1,69 @@
+use gl::*;
+use image::RgbaImage;
+
+use crate::{RenderTarget, RenderTargetHandle, RenderTargetSettings, Renderer};
+
+impl Renderer {
+    /// reads a rectangle of the currently bound framebuffer, (x, y) being its bottom
+    /// left corner. the image is flipped so its first row is the top one
+    pub unsafe fn read_pixels(&self, x: i32, y: i32, width: u32, height: u32) -> RgbaImage {
+        let mut pixels = vec![0u8; (width * height * 4) as usize];
+
+        // no padding between rows
+        PixelStorei(PACK_ALIGNMENT, 1);
+        ReadPixels(x, y, width as i32, height as i32, RGBA, UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut std::ffi::c_void);
+
+        let image = RgbaImage::from_raw(width, height, pixels).expect("the buffer matches the image size");
+        image::imageops::flip_vertical(&image)
+    }
+
+    /// everything inside the current viewport, from the back buffer before it is swapped
+    pub unsafe fn read_viewport(&self) -> RgbaImage {
+        let mut viewport = [0; 4];
+        GetIntegerv(VIEWPORT, viewport.as_mut_ptr());
+
+        self.read_pixels(viewport[0], viewport[1], viewport[2].max(0) as u32, viewport[3].max(0) as u32)
+    }
+
+    /// a color attachment of a render target
+    pub unsafe fn read_render_target(&self, handle: RenderTargetHandle, index: usize) -> Option<RgbaImage> {
+        let target = self.render_targets.get(handle)?;
+        if index >= target.color_textures.len() {
+            println!("Failed to read render target, it has no color attachment {}", index);
+            return None;
+        }
+
+        Some(read_target(self, target, index))
+    }
+
+    /// draws the scene, post processing included, into an off-screen target of the
+    /// given size and reads it back. works the same with a hidden window, which is
+    /// what golden image tests should use
+    pub unsafe fn capture(&self, width: u32, height: u32) -> Option<RgbaImage> {
+        let target = RenderTarget::new(RenderTargetSettings { width, height, ..Default::default() })?;
+
+        target.begin();
+        Clear(COLOR_BUFFER_BIT | DEPTH_BUFFER_BIT | STENCIL_BUFFER_BIT);
+        self.draw();
+        target.end();
+
+        Some(read_target(self, &target, 0))
+    }
+
+    /// saves what is currently in the viewport, the format is picked from the extension
+    pub unsafe fn screenshot(&self, path: &str) -> Result<(), image::ImageError> {
+        self.read_viewport().save(path)
+    }
+}
+
+unsafe fn read_target(renderer: &Renderer, target: &RenderTarget, index: usize) -> RgbaImage {
+    let mut previous = 0;
+    GetIntegerv(READ_FRAMEBUFFER_BINDING, &mut previous);
+
+    BindFramebuffer(READ_FRAMEBUFFER, target.fbo);
+    ReadBuffer(COLOR_ATTACHMENT0 + index as u32);
+    let image = renderer.read_pixels(0, 0, target.settings.width, target.settings.height);
+
+    BindFramebuffer(READ_FRAMEBUFFER, previous as u32);
+    image
+}
@@ -1,57 +1,223 @@
 use gl::*;
 use gl::types::*;
-use glam::Vec3;
+use glam::{IVec2, IVec3, IVec4, Mat4, Vec2, Vec3, Vec4};
 
+use crate::{bind_uniform_blocks, preprocess, Renderer};
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::ptr;
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub struct Shader {
     pub id: u32,
 }
 
-impl Shader {
-    pub fn new(shader_type: GLenum, code: &str) -> Self {
-        unsafe {
-            let shader = CreateShader(shader_type);
-            let cstr = std::ffi::CString::new(code.as_bytes()).unwrap();
-            ShaderSource(shader, 1, &cstr.as_ptr(), std::ptr::null());
-            CompileShader(shader);
-            check_shader_error(shader);
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Geometry,
+    Compute,
+}
 
-            let id = CreateProgram();
-            AttachShader(id, shader);
-            LinkProgram(id);
-            check_shader_link_error(id);
+impl ShaderStage {
+    pub fn gl_enum(&self) -> GLenum {
+        match self {
+            ShaderStage::Vertex => VERTEX_SHADER,
+            ShaderStage::Fragment => FRAGMENT_SHADER,
+            ShaderStage::Geometry => GEOMETRY_SHADER,
+            ShaderStage::Compute => COMPUTE_SHADER,
+        }
+    }
+}
 
-            DeleteShader(shader);
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShaderError {
+    /// `source` has every line prefixed with its number, to match the line numbers in `log`
+    Compile { stage: ShaderStage, log: String, source: String },
+    Link { log: String },
+    /// a source file couldn't be read
+    Io { path: PathBuf, message: String },
+    /// an #include couldn't be resolved
+    Preprocess { message: String },
+    /// compute shaders can't be linked together with other stages
+    MixedStages,
+    NoStages,
+}
 
-            Self { id }
+impl std::fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShaderError::Compile { stage, log, source } => {
+                write!(f, "ERROR::SHADER::{:?}::COMPILATION_FAILED\n{}\n{}", stage, log.trim_end(), source)
+            }
+            ShaderError::Link { log } => write!(f, "ERROR::SHADER::PROGRAM::LINKING_FAILED\n{}", log.trim_end()),
+            ShaderError::Io { path, message } => write!(f, "ERROR::SHADER::FILE: {}: {}", path.display(), message),
+            ShaderError::Preprocess { message } => write!(f, "ERROR::SHADER::PREPROCESSOR: {}", message),
+            ShaderError::MixedStages => write!(f, "ERROR::SHADER::PROGRAM: a compute shader can't share a program with other stages"),
+            ShaderError::NoStages => write!(f, "ERROR::SHADER::PROGRAM: no stages were given"),
         }
     }
-    
-    pub fn new_pipeline(vs_code: &str, fs_code: &str) -> Self {
+}
+
+impl std::error::Error for ShaderError {}
+
+/// collects the source of each stage, then preprocesses, compiles and links them all in `build`
+#[derive(Debug, Clone, Default)]
+pub struct ShaderBuilder {
+    pub stages: Vec<(ShaderStage, String)>,
+    /// injected into every stage, see `preprocess`
+    pub defines: Vec<(String, String)>,
+}
+
+impl ShaderBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stage(mut self, stage: ShaderStage, code: &str) -> Self {
+        self.stages.push((stage, code.to_string()));
+        self
+    }
+
+    pub fn vertex(self, code: &str) -> Self {
+        self.stage(ShaderStage::Vertex, code)
+    }
+
+    pub fn fragment(self, code: &str) -> Self {
+        self.stage(ShaderStage::Fragment, code)
+    }
+
+    pub fn geometry(self, code: &str) -> Self {
+        self.stage(ShaderStage::Geometry, code)
+    }
+
+    pub fn compute(self, code: &str) -> Self {
+        self.stage(ShaderStage::Compute, code)
+    }
+
+    /// adds `#define name value` to every stage
+    pub fn define(mut self, name: &str, value: impl ToString) -> Self {
+        self.defines.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn build(&self) -> Result<Shader, ShaderError> {
         unsafe {
-            let vs = CreateShader(VERTEX_SHADER);
-            let cstr = std::ffi::CString::new(vs_code.as_bytes()).unwrap();
-            ShaderSource(vs, 1, &cstr.as_ptr(), std::ptr::null());
-            CompileShader(vs);
-            check_shader_error(vs);
-
-            let fs = CreateShader(FRAGMENT_SHADER);
-            let cstr = std::ffi::CString::new(fs_code.as_bytes()).unwrap();
-            ShaderSource(fs, 1, &cstr.as_ptr(), std::ptr::null());
-            CompileShader(fs);
-            check_shader_error(fs);
+            let compiled = self.compile()?;
 
             let id = CreateProgram();
-            AttachShader(id, vs);
-            AttachShader(id, fs);
-            LinkProgram(id);
-            check_shader_link_error(id);
+            if let Err(err) = link(id, compiled) {
+                DeleteProgram(id);
+                return Err(err);
+            }
+
+            Ok(Shader { id })
+        }
+    }
+
+    /// recompiles into the program `shader` already is, so every copy of it keeps
+    /// working. the old program is left untouched if a stage doesn't compile, a link
+    /// error leaves it unusable until the sources are fixed
+    pub fn rebuild(&self, shader: Shader) -> Result<(), ShaderError> {
+        unsafe {
+            let compiled = self.compile()?;
+            link(shader.id, compiled)
+        }
+    }
+
+    /// preprocesses and compiles every stage, the caller links and deletes them
+    unsafe fn compile(&self) -> Result<Vec<GLuint>, ShaderError> {
+        if self.stages.is_empty() {
+            return Err(ShaderError::NoStages);
+        }
+        let has_compute = self.stages.iter().any(|(stage, _)| *stage == ShaderStage::Compute);
+        if has_compute && self.stages.len() > 1 {
+            return Err(ShaderError::MixedStages);
+        }
+
+        let sources = self.stages.iter()
+            .map(|(stage, code)| preprocess(code, &self.defines).map(|code| (*stage, code)))
+            .collect::<Result<Vec<_>, _>>()?;
 
-            Self { id }
+        let mut compiled = vec![];
+        for (stage, code) in &sources {
+            match compile_stage(*stage, code) {
+                Ok(shader) => compiled.push(shader),
+                Err(err) => {
+                    for shader in compiled {
+                        DeleteShader(shader);
+                    }
+                    return Err(err);
+                }
+            }
         }
+
+        Ok(compiled)
+    }
+}
+
+/// links the compiled stages into `id`, deleting them afterwards
+unsafe fn link(id: GLuint, compiled: Vec<GLuint>) -> Result<(), ShaderError> {
+    for shader in &compiled {
+        AttachShader(id, *shader);
+    }
+    LinkProgram(id);
+
+    // the program keeps what it needs, the stage objects can go either way
+    for shader in compiled {
+        DetachShader(id, shader);
+        DeleteShader(shader);
+    }
+
+    check_shader_link_error(id)?;
+
+    // linking resets every uniform, including the sampler units
+    bind_uniform_blocks(id);
+    reflect_uniforms(id);
+
+    Ok(())
+}
+
+impl Shader {
+    /// a program with a single stage, mostly useful for compute shaders
+    pub fn new(stage: ShaderStage, code: &str) -> Result<Self, ShaderError> {
+        ShaderBuilder::new().stage(stage, code).build()
+    }
+
+    pub fn new_pipeline(vs_code: &str, fs_code: &str) -> Result<Self, ShaderError> {
+        ShaderBuilder::new().vertex(vs_code).fragment(fs_code).build()
+    }
+
+    pub fn builder() -> ShaderBuilder {
+        ShaderBuilder::new()
+    }
+
+    /// like `new_pipeline` but reads the sources from disk. the files are
+    /// watched and the program is recompiled by `Renderer::update` when they change
+    pub fn from_files(vs_path: impl AsRef<Path>, fs_path: impl AsRef<Path>) -> Result<Self, ShaderError> {
+        let (vs_path, fs_path) = (vs_path.as_ref().to_path_buf(), fs_path.as_ref().to_path_buf());
+        let shader = Self::new_pipeline(&read_source(&vs_path)?, &read_source(&fs_path)?)?;
+
+        WATCHED_SHADERS.with(|watched| watched.borrow_mut().push(WatchedShader {
+            modified: last_modified(&[&vs_path, &fs_path]),
+            shader,
+            vs_path,
+            fs_path,
+        }));
+
+        Ok(shader)
+    }
+
+    /// frees the program, any copy of this shader is unusable afterwards
+    pub unsafe fn delete(&self) {
+        DeleteProgram(self.id);
+        UNIFORM_CACHE.with(|cache| cache.borrow_mut().remove(&self.id));
+        WATCHED_SHADERS.with(|watched| watched.borrow_mut().retain(|w| w.shader != *self));
     }
 
     pub unsafe fn use_shader(&self) {
@@ -62,66 +228,345 @@ impl Shader {
 
     }
 
+    /// every active uniform outside of a uniform block, as reflected after linking
+    pub fn uniforms(&self) -> Vec<UniformInfo> {
+        UNIFORM_CACHE.with(|cache| {
+            cache.borrow().get(&self.id).map_or(Vec::new(), |cache| cache.uniforms.clone())
+        })
+    }
+
+    /// looks the name up in the cache filled when the program was linked. array
+    /// elements can be addressed with `name[i]`, `name` alone is the first element
+    pub fn uniform_location(&self, name: &CStr) -> Result<GLint, UniformError> {
+        let name = name.to_string_lossy();
+
+        UNIFORM_CACHE.with(|cache| {
+            cache.borrow().get(&self.id)
+                .and_then(|cache| cache.locations.get(name.as_ref()).copied())
+                .ok_or_else(|| UniformError::NotFound { program: self.id, name: name.to_string() })
+        })
+    }
+
+    /* the setters below skip unknown names and warn about each one once per program */
+    fn location_or_warn(&self, name: &CStr) -> Option<GLint> {
+        match self.uniform_location(name) {
+            Ok(location) => Some(location),
+            Err(err) => {
+                let first_time = UNIFORM_CACHE.with(|cache| {
+                    cache.borrow_mut().entry(self.id).or_default().missing.insert(name.to_string_lossy().to_string())
+                });
+                if first_time {
+                    println!("{}", err);
+                }
+                None
+            }
+        }
+    }
+
     pub unsafe fn uniform_1f(&self, name: &CStr, val: f32) {
-        Uniform1f(GetUniformLocation(self.id, name.as_ptr()), val);
+        if let Some(location) = self.location_or_warn(name) {
+            Uniform1f(location, val);
+        }
     }
+
     pub unsafe fn uniform_1i(&self, name: &CStr, val: i32) {
-        Uniform1i(
-            GetUniformLocation(self.id, name.as_ptr()), val);
+        if let Some(location) = self.location_or_warn(name) {
+            Uniform1i(location, val);
+        }
     }
 
-    pub unsafe fn uniform_mat4fv(&self, name: &CStr, mat: &[f32; 16]) {
-        UniformMatrix4fv(
-            GetUniformLocation(self.id, name.as_ptr()), 
-            1, 
-            FALSE, 
-            mat as *const f32
-        );
+    pub unsafe fn uniform_vec2f(&self, name: &CStr, vec: &Vec2) {
+        if let Some(location) = self.location_or_warn(name) {
+            Uniform2f(location, vec.x, vec.y);
+        }
     }
 
     pub unsafe fn uniform_vec3f(&self, name: &CStr, vec: &Vec3) {
-        Uniform3f(
-            GetUniformLocation(self.id, name.as_ptr()),
-            vec.x, vec.y, vec.z
-        );
+        if let Some(location) = self.location_or_warn(name) {
+            Uniform3f(location, vec.x, vec.y, vec.z);
+        }
     }
+
+    pub unsafe fn uniform_vec4f(&self, name: &CStr, vec: &Vec4) {
+        if let Some(location) = self.location_or_warn(name) {
+            Uniform4f(location, vec.x, vec.y, vec.z, vec.w);
+        }
+    }
+
+    pub unsafe fn uniform_ivec2(&self, name: &CStr, vec: &IVec2) {
+        if let Some(location) = self.location_or_warn(name) {
+            Uniform2i(location, vec.x, vec.y);
+        }
+    }
+
+    pub unsafe fn uniform_ivec3(&self, name: &CStr, vec: &IVec3) {
+        if let Some(location) = self.location_or_warn(name) {
+            Uniform3i(location, vec.x, vec.y, vec.z);
+        }
+    }
+
+    pub unsafe fn uniform_ivec4(&self, name: &CStr, vec: &IVec4) {
+        if let Some(location) = self.location_or_warn(name) {
+            Uniform4i(location, vec.x, vec.y, vec.z, vec.w);
+        }
+    }
+
+    pub unsafe fn uniform_mat3fv(&self, name: &CStr, mat: &[f32; 9]) {
+        if let Some(location) = self.location_or_warn(name) {
+            UniformMatrix3fv(location, 1, FALSE, mat as *const f32);
+        }
+    }
+
+    pub unsafe fn uniform_mat4fv(&self, name: &CStr, mat: &[f32; 16]) {
+        if let Some(location) = self.location_or_warn(name) {
+            UniformMatrix4fv(location, 1, FALSE, mat as *const f32);
+        }
+    }
+
+    /// binds a sampler uniform to a texture unit
+    pub unsafe fn uniform_sampler(&self, name: &CStr, unit: u32) {
+        self.uniform_1i(name, unit as i32);
+    }
+
+    /* arrays, starting at the element `name` points to */
+    pub unsafe fn uniform_1fv(&self, name: &CStr, vals: &[f32]) {
+        if let Some(location) = self.location_or_warn(name) {
+            Uniform1fv(location, vals.len() as GLsizei, vals.as_ptr());
+        }
+    }
+
+    pub unsafe fn uniform_1iv(&self, name: &CStr, vals: &[i32]) {
+        if let Some(location) = self.location_or_warn(name) {
+            Uniform1iv(location, vals.len() as GLsizei, vals.as_ptr());
+        }
+    }
+
+    pub unsafe fn uniform_vec2fv(&self, name: &CStr, vecs: &[Vec2]) {
+        if let Some(location) = self.location_or_warn(name) {
+            Uniform2fv(location, vecs.len() as GLsizei, vecs.as_ptr() as *const f32);
+        }
+    }
+
+    pub unsafe fn uniform_vec3fv(&self, name: &CStr, vecs: &[Vec3]) {
+        if let Some(location) = self.location_or_warn(name) {
+            Uniform3fv(location, vecs.len() as GLsizei, vecs.as_ptr() as *const f32);
+        }
+    }
+
+    pub unsafe fn uniform_vec4fv(&self, name: &CStr, vecs: &[Vec4]) {
+        if let Some(location) = self.location_or_warn(name) {
+            Uniform4fv(location, vecs.len() as GLsizei, vecs.as_ptr() as *const f32);
+        }
+    }
+
+    pub unsafe fn uniform_mat4fv_array(&self, name: &CStr, mats: &[Mat4]) {
+        if let Some(location) = self.location_or_warn(name) {
+            UniformMatrix4fv(location, mats.len() as GLsizei, FALSE, mats.as_ptr() as *const f32);
+        }
+    }
+}
+
+/// an active uniform of a linked program
+#[derive(Debug, Clone, PartialEq)]
+pub struct UniformInfo {
+    /// without the `[0]` suffix for arrays
+    pub name: String,
+    /// the glsl type, e.g. FLOAT_VEC3 or SAMPLER_2D
+    pub ty: GLenum,
+    /// number of elements, 1 if it isn't an array
+    pub size: i32,
+    pub location: GLint,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UniformError {
+    NotFound { program: u32, name: String },
+}
+
+impl std::fmt::Display for UniformError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UniformError::NotFound { program, name } => {
+                write!(f, "WARNING::SHADER::UNIFORM: program {} has no active uniform named \"{}\"", program, name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UniformError {}
+
+#[derive(Default)]
+struct UniformCache {
+    uniforms: Vec<UniformInfo>,
+    locations: HashMap<String, GLint>,
+    /* names that were already warned about */
+    missing: HashSet<String>,
+}
+
+// keyed by program id so `Shader` can stay Copy. opengl only lives on one thread anyway
+thread_local! {
+    static UNIFORM_CACHE: RefCell<HashMap<u32, UniformCache>> = RefCell::new(HashMap::new());
+}
+
+/// reads every active uniform of a freshly linked program into the cache
+pub unsafe fn reflect_uniforms(program: u32) {
+    let mut cache = UniformCache::default();
+
+    let mut count = 0;
+    GetProgramiv(program, ACTIVE_UNIFORMS, &mut count);
+    let mut max_length = 0;
+    GetProgramiv(program, ACTIVE_UNIFORM_MAX_LENGTH, &mut max_length);
+
+    for index in 0..count as u32 {
+        let mut buffer = vec![0u8; max_length.max(1) as usize];
+        let mut length = 0;
+        let mut size = 0;
+        let mut ty = 0;
+        GetActiveUniform(program, index, max_length, &mut length, &mut size, &mut ty, buffer.as_mut_ptr() as *mut GLchar);
+        buffer.truncate(length as usize);
+
+        let full_name = String::from_utf8_lossy(&buffer).to_string();
+        let location = GetUniformLocation(program, CString::new(full_name.as_str()).unwrap().as_ptr());
+        // members of uniform blocks have no location of their own
+        if location == -1 { continue; }
+
+        let name = full_name.strip_suffix("[0]").unwrap_or(&full_name).to_string();
+        cache.locations.insert(name.clone(), location);
+
+        if full_name.ends_with("[0]") {
+            for i in 0..size {
+                let element = format!("{}[{}]", name, i);
+                let element_location = GetUniformLocation(program, CString::new(element.as_str()).unwrap().as_ptr());
+                cache.locations.insert(element, element_location);
+            }
+        }
+
+        cache.uniforms.push(UniformInfo { name, ty, size, location });
+    }
+
+    UNIFORM_CACHE.with(|c| c.borrow_mut().insert(program, cache));
+}
+
+struct WatchedShader {
+    shader: Shader,
+    vs_path: PathBuf,
+    fs_path: PathBuf,
+    /* newest modification time of the two files when they were last compiled */
+    modified: Option<SystemTime>,
+}
+
+thread_local! {
+    static WATCHED_SHADERS: RefCell<Vec<WatchedShader>> = RefCell::new(Vec::new());
 }
 
-pub unsafe fn check_shader_error(shader: u32) {
+fn read_source(path: &Path) -> Result<String, ShaderError> {
+    std::fs::read_to_string(path)
+        .map_err(|err| ShaderError::Io { path: path.to_path_buf(), message: err.to_string() })
+}
+
+fn last_modified(paths: &[&Path]) -> Option<SystemTime> {
+    paths.iter()
+        .filter_map(|path| std::fs::metadata(path).and_then(|meta| meta.modified()).ok())
+        .max()
+}
+
+/// recompiles every shader from `Shader::from_files` whose files changed into the
+/// program it already is. on failure the error is printed and the old program
+/// stays in use until the files change again
+fn reload_changed_shaders() {
+    // rebuilding deletes a scratch program, which looks through the watched shaders
+    let changed = WATCHED_SHADERS.with(|watched| {
+        let mut changed = vec![];
+
+        for watched in watched.borrow_mut().iter_mut() {
+            let modified = last_modified(&[&watched.vs_path, &watched.fs_path]);
+            if modified == watched.modified { continue; }
+            watched.modified = modified;
+
+            changed.push((watched.shader, watched.vs_path.clone(), watched.fs_path.clone()));
+        }
+
+        changed
+    });
+
+    for (shader, vs_path, fs_path) in changed {
+        let result = read_source(&vs_path)
+            .and_then(|vs| read_source(&fs_path).map(|fs| (vs, fs)))
+            .and_then(|(vs, fs)| ShaderBuilder::new().vertex(&vs).fragment(&fs).rebuild(shader));
+
+        if let Err(err) = result {
+            println!("Failed to reload shader, keeping the old one\n{}", err);
+        }
+    }
+}
+
+impl Renderer {
+    /// recompiles the shaders whose files changed. the program ids stay the same,
+    /// so materials and any copies of the shaders pick the changes up as they are
+    pub(crate) fn reload_shaders(&mut self) {
+        reload_changed_shaders();
+    }
+}
+
+unsafe fn compile_stage(stage: ShaderStage, code: &str) -> Result<GLuint, ShaderError> {
+    let shader = CreateShader(stage.gl_enum());
+    let cstr = CString::new(code.as_bytes()).unwrap();
+    ShaderSource(shader, 1, &cstr.as_ptr(), ptr::null());
+    CompileShader(shader);
+
+    if let Err(err) = check_shader_error(shader, stage, code) {
+        DeleteShader(shader);
+        return Err(err);
+    }
+
+    Ok(shader)
+}
+
+pub unsafe fn check_shader_error(shader: u32, stage: ShaderStage, code: &str) -> Result<(), ShaderError> {
     let mut success = gl::FALSE as GLint;
-    let mut info_log = Vec::with_capacity(512);
-    info_log.set_len(54); // skip the trailing null char
     GetShaderiv(shader, COMPILE_STATUS, &mut success);
-    if success != gl::TRUE as GLint {
-        GetShaderInfoLog(
-            shader,
-            512,
-            ptr::null_mut(),
-            info_log.as_mut_ptr() as *mut GLchar,
-        );
-        println!(
-            "ERROR::SHADER::COMPILATION::FAILED\n{}",
-            std::str::from_utf8(&info_log).unwrap()
-        );
+    if success == gl::TRUE as GLint {
+        return Ok(());
     }
+
+    let mut length = 0;
+    GetShaderiv(shader, INFO_LOG_LENGTH, &mut length);
+    let mut info_log = vec![0u8; length.max(1) as usize];
+    let mut written = 0;
+    GetShaderInfoLog(shader, length, &mut written, info_log.as_mut_ptr() as *mut GLchar);
+    info_log.truncate(written as usize);
+
+    Err(ShaderError::Compile {
+        stage,
+        log: String::from_utf8_lossy(&info_log).to_string(),
+        source: annotate_lines(code),
+    })
 }
 
-pub unsafe fn check_shader_link_error(program: u32) {
+pub unsafe fn check_shader_link_error(program: u32) -> Result<(), ShaderError> {
     let mut success = gl::FALSE as GLint;
-    let mut info_log = Vec::with_capacity(512);
-    gl::GetProgramiv(program, gl::LINK_STATUS, &mut success);
-    if success != gl::TRUE as GLint {
-        gl::GetProgramInfoLog(
-            program,
-            512,
-            ptr::null_mut(),
-            info_log.as_mut_ptr() as *mut GLchar,
-        );
-        println!(
-            "ERROR::SHADER::PROGRAM::COMPILATION_FAILED\n{}",
-            std::str::from_utf8(&info_log).unwrap()
-        );
+    GetProgramiv(program, LINK_STATUS, &mut success);
+    if success == gl::TRUE as GLint {
+        return Ok(());
     }
+
+    let mut length = 0;
+    GetProgramiv(program, INFO_LOG_LENGTH, &mut length);
+    let mut info_log = vec![0u8; length.max(1) as usize];
+    let mut written = 0;
+    GetProgramInfoLog(program, length, &mut written, info_log.as_mut_ptr() as *mut GLchar);
+    info_log.truncate(written as usize);
+
+    Err(ShaderError::Link { log: String::from_utf8_lossy(&info_log).to_string() })
+}
+
+/// prefixes every line with its number, starting at 1 like the driver logs do
+fn annotate_lines(code: &str) -> String {
+    code.lines()
+        .enumerate()
+        .map(|(i, line)| format!("{:4} | {}", i + 1, line))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 #[macro_export]
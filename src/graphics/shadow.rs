@@ -0,0 +1,307 @@
+use std::ffi::CString;
+
+use gl::*;
+use gl::types::GLint;
+use glam::{Mat4, Vec3};
+
+use crate::{cstr, Camera, Light, LightKind, Renderer, MAX_LIGHTS, SHADOW_INSTANCED_SHADER, SHADOW_SHADER, SHADOW_SKINNED_SHADER};
+
+/// at most this many lights get a shadow map, the rest are lit without occlusion
+pub const MAX_SHADOW_MAPS: usize = 4;
+/// texture units from here onwards are reserved for shadow maps, the first
+/// MAX_SHADOW_MAPS for cubemaps and the next MAX_SHADOW_MAPS for 2D maps
+pub const SHADOW_MAP_TEXTURE_UNIT: u32 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowSettings {
+    /// width and height of the shadow map (of each face, for point lights)
+    pub resolution: u32,
+    /// pushes the compared depth away from the surface to avoid shadow acne
+    pub bias: f32,
+    /// radius of the percentage closer filtering kernel, 0 takes a single sample
+    pub pcf: i32,
+    /// anything further than this from the light is never shadowed
+    pub far: f32,
+    /// half the width of the area around the camera covered by a directional light
+    pub extent: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 1024,
+            bias: 0.05,
+            pcf: 1,
+            far: 100.0,
+            extent: 30.0,
+        }
+    }
+}
+
+/// a depth texture rendered from a light's point of view. point lights
+/// get a cubemap, directional and spot lights a single 2D map
+#[derive(Debug)]
+pub struct ShadowMap {
+    pub fbo: u32,
+    pub depth_texture: u32,
+    pub resolution: u32,
+    pub cube: bool,
+}
+
+impl ShadowMap {
+    pub fn new_cube(resolution: u32) -> Self {
+        let mut depth_texture = 0;
+
+        unsafe {
+            GenTextures(1, &mut depth_texture);
+            BindTexture(TEXTURE_CUBE_MAP, depth_texture);
+            for face in 0..6 {
+                TexImage2D(
+                    TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                    0,
+                    DEPTH_COMPONENT as GLint,
+                    resolution as i32,
+                    resolution as i32,
+                    0,
+                    DEPTH_COMPONENT,
+                    FLOAT,
+                    std::ptr::null(),
+                );
+            }
+            TexParameteri(TEXTURE_CUBE_MAP, TEXTURE_MIN_FILTER, NEAREST as GLint);
+            TexParameteri(TEXTURE_CUBE_MAP, TEXTURE_MAG_FILTER, NEAREST as GLint);
+            TexParameteri(TEXTURE_CUBE_MAP, TEXTURE_WRAP_S, CLAMP_TO_EDGE as GLint);
+            TexParameteri(TEXTURE_CUBE_MAP, TEXTURE_WRAP_T, CLAMP_TO_EDGE as GLint);
+            TexParameteri(TEXTURE_CUBE_MAP, TEXTURE_WRAP_R, CLAMP_TO_EDGE as GLint);
+            BindTexture(TEXTURE_CUBE_MAP, 0);
+        }
+
+        Self::with_depth_texture(depth_texture, TEXTURE_CUBE_MAP_POSITIVE_X, resolution, true)
+    }
+
+    pub fn new_flat(resolution: u32) -> Self {
+        let mut depth_texture = 0;
+
+        unsafe {
+            GenTextures(1, &mut depth_texture);
+            BindTexture(TEXTURE_2D, depth_texture);
+            TexImage2D(
+                TEXTURE_2D,
+                0,
+                DEPTH_COMPONENT as GLint,
+                resolution as i32,
+                resolution as i32,
+                0,
+                DEPTH_COMPONENT,
+                FLOAT,
+                std::ptr::null(),
+            );
+            TexParameteri(TEXTURE_2D, TEXTURE_MIN_FILTER, NEAREST as GLint);
+            TexParameteri(TEXTURE_2D, TEXTURE_MAG_FILTER, NEAREST as GLint);
+            // everything outside of the map counts as lit
+            TexParameteri(TEXTURE_2D, TEXTURE_WRAP_S, CLAMP_TO_BORDER as GLint);
+            TexParameteri(TEXTURE_2D, TEXTURE_WRAP_T, CLAMP_TO_BORDER as GLint);
+            TexParameterfv(TEXTURE_2D, TEXTURE_BORDER_COLOR, [1.0f32; 4].as_ptr());
+            BindTexture(TEXTURE_2D, 0);
+        }
+
+        Self::with_depth_texture(depth_texture, TEXTURE_2D, resolution, false)
+    }
+
+    fn with_depth_texture(depth_texture: u32, target: u32, resolution: u32, cube: bool) -> Self {
+        let mut fbo = 0;
+
+        unsafe {
+            // depth only, there is no color attachment to draw to
+            GenFramebuffers(1, &mut fbo);
+            BindFramebuffer(FRAMEBUFFER, fbo);
+            FramebufferTexture2D(FRAMEBUFFER, DEPTH_ATTACHMENT, target, depth_texture, 0);
+            DrawBuffer(NONE);
+            ReadBuffer(NONE);
+            BindFramebuffer(FRAMEBUFFER, 0);
+        }
+
+        Self {
+            fbo,
+            depth_texture,
+            resolution,
+            cube,
+        }
+    }
+}
+
+impl Drop for ShadowMap {
+    fn drop(&mut self) {
+        unsafe {
+            DeleteFramebuffers(1, &self.fbo);
+            DeleteTextures(1, &self.depth_texture);
+        }
+    }
+}
+
+/// view-projection matrices for the 6 faces of a cubemap centered on `pos`
+pub fn cube_face_matrices(pos: Vec3, far: f32) -> [Mat4; 6] {
+    let proj = Mat4::perspective_rh_gl(90.0_f32.to_radians(), 1.0, 0.1, far);
+    let look = |dir: Vec3, up: Vec3| proj * Mat4::look_at_rh(pos, pos + dir, up);
+
+    [
+        look(Vec3::X, -Vec3::Y),
+        look(-Vec3::X, -Vec3::Y),
+        look(Vec3::Y, Vec3::Z),
+        look(-Vec3::Y, -Vec3::Z),
+        look(Vec3::Z, -Vec3::Y),
+        look(-Vec3::Z, -Vec3::Y),
+    ]
+}
+
+/// where a 2D shadow map is rendered from and its view-projection matrix.
+/// directional lights cover a box of `extent` around the camera
+pub fn flat_shadow_view(light: &Light, settings: &ShadowSettings, camera: &Camera) -> (Vec3, Mat4) {
+    let up_for = |dir: Vec3| if dir.normalize().y.abs() > 0.99 { Vec3::Z } else { Vec3::Y };
+
+    match light.kind {
+        LightKind::Directional { direction } => {
+            let direction = direction.normalize();
+            let origin = camera.pos - direction * settings.far * 0.5;
+            let e = settings.extent;
+            let proj = Mat4::orthographic_rh_gl(-e, e, -e, e, 0.1, settings.far);
+
+            (origin, proj * Mat4::look_at_rh(origin, camera.pos, up_for(direction)))
+        }
+        LightKind::Spot { direction, outer_cone, .. } => {
+            let proj = Mat4::perspective_rh_gl((outer_cone * 2.0).min(3.1), 1.0, 0.1, settings.far);
+
+            (light.position, proj * Mat4::look_at_rh(light.position, light.position + direction, up_for(direction)))
+        }
+        LightKind::Point => (light.position, Mat4::IDENTITY),
+    }
+}
+
+impl Renderer {
+    /// creates, resizes or frees shadow maps so they match the lights' settings
+    pub(crate) fn update_shadow_maps(&mut self) {
+        // lights past MAX_LIGHTS aren't uploaded, so there's nothing to shadow
+        let shadowed = self.lights.iter()
+            .take(MAX_LIGHTS)
+            .filter_map(|(handle, light)| light.shadow.map(|settings| (handle, light.kind, settings)))
+            .take(MAX_SHADOW_MAPS)
+            .collect::<Vec<_>>();
+
+        // lights that lost their shadow or fell out of the first MAX_SHADOW_MAPS or MAX_LIGHTS
+        self.shadow_maps.retain(|handle, _| {
+            shadowed.iter().any(|(shadowed, ..)| shadowed == handle)
+        });
+
+        for (handle, kind, settings) in shadowed {
+            let cube = kind == LightKind::Point;
+            let outdated = self.shadow_maps.get(&handle)
+                .map_or(true, |map| map.resolution != settings.resolution || map.cube != cube);
+
+            if outdated {
+                let map = if cube {
+                    ShadowMap::new_cube(settings.resolution)
+                } else {
+                    ShadowMap::new_flat(settings.resolution)
+                };
+
+                self.shadow_maps.insert(handle, map);
+            }
+        }
+    }
+
+    /// renders the depth of every shadow casting mesh into each shadow map,
+    /// then binds the maps to their reserved texture units
+    pub(crate) unsafe fn render_shadow_maps(&self) {
+        if self.shadow_maps.is_empty() { return; }
+
+        let mut viewport = [0; 4];
+        GetIntegerv(VIEWPORT, viewport.as_mut_ptr());
+        let mut previous_fbo = 0;
+        GetIntegerv(FRAMEBUFFER_BINDING, &mut previous_fbo);
+
+        for (handle, light) in self.lights.iter() {
+            let (Some(map), Some(settings)) = (self.shadow_maps.get(&handle), light.shadow) else {
+                continue;
+            };
+
+            BindFramebuffer(FRAMEBUFFER, map.fbo);
+            Viewport(0, 0, map.resolution as i32, map.resolution as i32);
+
+            if map.cube {
+                for (face, matrix) in cube_face_matrices(light.position, settings.far).iter().enumerate() {
+                    FramebufferTexture2D(
+                        FRAMEBUFFER,
+                        DEPTH_ATTACHMENT,
+                        TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+                        map.depth_texture,
+                        0
+                    );
+                    self.render_depth(light.position, settings.far, matrix);
+                }
+            } else {
+                let (origin, matrix) = flat_shadow_view(light, &settings, &self.camera);
+                self.render_depth(origin, settings.far, &matrix);
+            }
+        }
+
+        UseProgram(0);
+        BindFramebuffer(FRAMEBUFFER, previous_fbo as u32);
+        Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+
+        for (slot, (_, _, map, _)) in self.shadow_map_slots().enumerate() {
+            if map.cube {
+                ActiveTexture(TEXTURE0 + SHADOW_MAP_TEXTURE_UNIT + slot as u32);
+                BindTexture(TEXTURE_CUBE_MAP, map.depth_texture);
+            } else {
+                ActiveTexture(TEXTURE0 + SHADOW_MAP_TEXTURE_UNIT + (MAX_SHADOW_MAPS + slot) as u32);
+                BindTexture(TEXTURE_2D, map.depth_texture);
+            }
+        }
+        ActiveTexture(TEXTURE0);
+    }
+
+    /// draws every caster into the currently bound depth attachment, storing
+    /// the linear distance to `origin` divided by `far`
+    unsafe fn render_depth(&self, origin: Vec3, far: f32, light_space: &Mat4) {
+        Clear(DEPTH_BUFFER_BIT);
+
+        SHADOW_SHADER.use_shader();
+        SHADOW_SHADER.uniform_vec3f(cstr!("lightPos"), &origin);
+        SHADOW_SHADER.uniform_1f(cstr!("far_plane"), far);
+        SHADOW_SHADER.uniform_mat4fv(cstr!("lightSpace"), &light_space.to_cols_array());
+        for mesh in self.meshes.values() {
+            mesh.draw_depth(&SHADOW_SHADER);
+        }
+        for model in self.models.values() {
+            for mesh in &model.meshes {
+                mesh.draw_depth(&SHADOW_SHADER);
+            }
+        }
+
+        SHADOW_INSTANCED_SHADER.use_shader();
+        SHADOW_INSTANCED_SHADER.uniform_vec3f(cstr!("lightPos"), &origin);
+        SHADOW_INSTANCED_SHADER.uniform_1f(cstr!("far_plane"), far);
+        SHADOW_INSTANCED_SHADER.uniform_mat4fv(cstr!("lightSpace"), &light_space.to_cols_array());
+        for mesh in self.instanced_meshes.values() {
+            mesh.draw_depth();
+        }
+
+        SHADOW_SKINNED_SHADER.use_shader();
+        SHADOW_SKINNED_SHADER.uniform_vec3f(cstr!("lightPos"), &origin);
+        SHADOW_SKINNED_SHADER.uniform_1f(cstr!("far_plane"), far);
+        SHADOW_SKINNED_SHADER.uniform_mat4fv(cstr!("lightSpace"), &light_space.to_cols_array());
+        for mesh in self.skeletal_meshes.values() {
+            mesh.draw_depth(&SHADOW_SKINNED_SHADER);
+        }
+    }
+
+    /// shadow maps in the order their slots are assigned, along with the index of their
+    /// light in the lights block. only the lights that are uploaded get a slot
+    pub(crate) fn shadow_map_slots(&self) -> impl Iterator<Item = (usize, &Light, &ShadowMap, ShadowSettings)> {
+        self.lights.iter()
+            .take(MAX_LIGHTS)
+            .enumerate()
+            .filter_map(|(i, (handle, light))| Some((i, light, self.shadow_maps.get(&handle)?, light.shadow?)))
+            .take(MAX_SHADOW_MAPS)
+    }
+}
@@ -4,9 +4,11 @@ use glam::{Vec2, Vec3};
 
 use gl::*;
 
-use crate::{bind_buffer, gen_attrib_pointers, Mesh, RUSSIMP_SHADER};
+use crate::{bind_buffer, gen_attrib_pointers};
 
 pub const MAX_BONE_INFLUENCE: usize = 4;
+/// length of `finalBonesMatrices` in the skinning shaders
+pub const MAX_BONES: usize = 100;
 
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub struct SkeletalVertex {
@@ -33,10 +35,9 @@ impl Default for SkeletalVertex {
     }
 }
 
-use crate::{cstr, Renderer, Shader, TextureHandle};
+use crate::{cstr, BlendMode, Handle, MaterialHandle, RenderQueue, Renderer, Shader};
 
-use std::ops::{Index, IndexMut};
-use std::{collections::HashMap, ptr};
+use std::ptr;
 
 use std::ffi::CString;
 
@@ -49,21 +50,30 @@ pub struct SkeletalMesh {
     pub indices: Vec<u32>,
 
     pub vao: u32,
-    pub texture: u32,
     pub ebo: u32,
     pub vbo: u32,
 
     pub position: Vec3,
     pub rotation: Quat,
     pub scale: Vec3,
-    pub color: Vec3,
 
-    pub shader: Shader,
-    pub parent: Option<Box<Mesh>>,
-    pub children: Vec<Box<Mesh>>,
+    /// drawn with the renderer's default skeletal material when None
+    pub material: Option<MaterialHandle>,
+    /// the world matrix of the scene node owning it, kept up to date by `Renderer::update`
+    pub node_transform: Mat4,
+
+    /// the pose, uploaded as `finalBonesMatrices` before every draw and shadow pass. when empty the
+    /// material's shader keeps whatever was set on it and the shadows use the bind pose
+    pub bones: Vec<Mat4>,
 
     pub has_been_set_up: bool,
     pub hidden: bool,
+    pub cast_shadows: bool,
+    pub receive_shadows: bool,
+    /// which queue it's drawn in, see `RenderQueue`
+    pub queue: RenderQueue,
+    /// the queue's default blend mode when None
+    pub blend: Option<BlendMode>,
 }
 
 impl SkeletalMesh {
@@ -74,19 +84,21 @@ impl SkeletalMesh {
             position: Vec3::ZERO,
             rotation: Quat::from_euler(glam::EulerRot::XYZ, 0.0, 0.0, 0.0),
             scale: Vec3::ONE,
-            texture: 0,
-            color: Vec3::ONE,
-            shader: *RUSSIMP_SHADER,
-            parent: None,
-            children: Vec::new(),
+            material: None,
+            node_transform: Mat4::IDENTITY,
+            bones: vec![],
             has_been_set_up: false,
             hidden: false,
+            cast_shadows: true,
+            receive_shadows: true,
+            queue: RenderQueue::Opaque,
+            blend: None,
         };
 
         mesh
     }
-    pub fn set_texture(&mut self, texture_handle: TextureHandle, renderer: &Renderer) {
-        self.texture = renderer.textures[&texture_handle];
+    pub fn set_material(&mut self, material: MaterialHandle) {
+        self.material = Some(material);
     }
 
     /* 
@@ -101,63 +113,43 @@ impl SkeletalMesh {
 
         new_mesh
     }
-
-    pub fn set_parent(&mut self, parent: Mesh){
-        self.parent = Some(Box::new(parent));
-    }
-
-    pub fn add_child(&mut self, mut child: Mesh){
-        child.set_parent(self.clone());
-        self.children.push(Box::new(child));
-    }
     */
 
-    pub fn set_color(&mut self, color: Vec3){
-        self.color = color;
-    }
-
-    pub fn set_position(&mut self, position: Vec3){
+    pub fn set_position(&mut self, position: Vec3) {
         self.position = position;
-        for child in self.children.as_mut_slice(){
-            child.set_position(position + child.position)
-        }
     }
 
-    pub fn add_position(&mut self, position: Vec3){
+    pub fn add_position(&mut self, position: Vec3) {
         self.position += position;
-        for child in self.children.as_mut_slice(){
-            child.add_position(position)
-        }
     }
 
-    pub fn set_scale(&mut self, scale: Vec3){
+    pub fn set_scale(&mut self, scale: Vec3) {
         self.scale = scale;
-        for child in self.children.as_mut_slice(){
-            child.set_scale(scale);
-        }
     }
 
-    pub fn scale(&mut self, scale: Vec3){
+    pub fn scale(&mut self, scale: Vec3) {
         self.scale *= scale;
-        for child in self.children.as_mut_slice(){
-            child.scale(scale);
-        }
     }
 
-    pub fn set_rotation(&mut self, rotation: Quat){
+    pub fn set_rotation(&mut self, rotation: Quat) {
         self.rotation = rotation;
-        for child in self.children.as_mut_slice(){
-            child.set_rotation(rotation);
-        }
     }
 
-    pub fn rotate(&mut self, rotation: Quat){
-        self.rotation = self.rotation + rotation;
-        for child in self.children.as_mut_slice(){
-            child.rotate(rotation);
-        }
+    pub fn rotate(&mut self, rotation: Quat) {
+        self.rotation = rotation * self.rotation;
+    }
+
+    /// position, rotation and scale on top of the transform of the node owning it
+    pub fn model_matrix(&self) -> Mat4 {
+        self.node_transform *
+        Mat4::from_translation(self.position) *
+        Mat4::from_quat(self.rotation) *
+        Mat4::from_scale(self.scale)
     }
 
+    pub fn world_position(&self) -> Vec3 {
+        self.node_transform.transform_point3(self.position)
+    }
 
     pub fn setup_mesh(&mut self) {
         unsafe {
@@ -175,16 +167,14 @@ impl SkeletalMesh {
     
             // ids
             EnableVertexAttribArray(3);
-            let offset_bone = &((*std::ptr::null::<SkeletalVertex>()).bone_ids) as *const _ as *const std::ffi::c_void;
+            let offset_bone = std::mem::offset_of!(SkeletalVertex, bone_ids) as *const std::ffi::c_void;
             VertexAttribIPointer(3, 4, INT, size, offset_bone);
     
             // weights (we could generate this using the macro, but i prefer this)
             EnableVertexAttribArray(4);
-            let offset_weight = &((*std::ptr::null::<SkeletalVertex>()).weights) as *const _ as *const std::ffi::c_void;
+            let offset_weight = std::mem::offset_of!(SkeletalVertex, weights) as *const std::ffi::c_void;
             VertexAttribPointer(4, 4, FLOAT, FALSE, size, offset_weight);
     
-            gl::BindTexture(gl::TEXTURE_2D, self.texture);
-    
             BindVertexArray(0);
         }
     }
@@ -192,33 +182,19 @@ impl SkeletalMesh {
     pub unsafe fn draw(&self, renderer: &Renderer) {
         if self.hidden { return; }
 
-        let model_matrix = 
-            Mat4::from_translation(self.position) *
-            Mat4::from_quat(self.rotation) *
-            Mat4::from_scale(self.scale);
+        let model_matrix = self.model_matrix();
+
+        let material = renderer.material_or(self.material, renderer.default_skeletal_material);
 
         BindVertexArray(self.vao);
-        self.shader.use_shader();
-        
-        if self.texture != 0 {
-            unsafe {
-                self.shader.uniform_1i(cstr!("has_texture"), 1);
-            }
-        } else {
-            unsafe {
-                self.shader.uniform_1i(cstr!("has_texture"), 0);
-            }
-        }
-        
-        BindTexture(TEXTURE_2D, self.texture);
+        material.bind();
 
         // Set uniforms and draw
-        self.shader.use_shader();
-        self.shader.uniform_mat4fv(cstr!("model"), &model_matrix.to_cols_array());
-        self.shader.uniform_vec3f(cstr!("pos"), &self.position);
-        self.shader.uniform_vec3f(cstr!("color"), &self.color);
-        renderer.send_light_uniforms(&self.shader);
-        renderer.camera.send_uniforms(&self.shader);
+        material.shader.uniform_mat4fv(cstr!("model"), &model_matrix.to_cols_array());
+        material.shader.uniform_1i(cstr!("receive_shadows"), self.receive_shadows as i32);
+        if !self.bones.is_empty() {
+            material.shader.uniform_mat4fv_array(cstr!("finalBonesMatrices"), &self.bones);
+        }
         
         DrawElements(TRIANGLES, self.indices.len() as i32, UNSIGNED_INT, ptr::null());
         
@@ -226,46 +202,45 @@ impl SkeletalMesh {
         BindVertexArray(0);
         UseProgram(0);
     }
-}
 
-impl Renderer {
-    pub fn add_skeletal_mesh_from_vertices_and_indices(&mut self, vertices: Vec<SkeletalVertex>, indices: Vec<u32>) -> Option<SkeletalMeshHandle> {
-        let handle = SkeletalMeshHandle {id: self.meshes.len()};
+    /// draws only the geometry with an already bound skinned depth shader, for shadow maps
+    pub unsafe fn draw_depth(&self, shader: &Shader) {
+        if self.hidden || !self.cast_shadows { return; }
 
-        if self.skeletal_meshes.contains_key(&handle) {
-            println!("Skeletal mesh with handle {:?} already exists", handle);
-            return None;
+        shader.uniform_mat4fv(cstr!("model"), &self.model_matrix().to_cols_array());
+        if self.bones.is_empty() {
+            shader.uniform_mat4fv_array(cstr!("finalBonesMatrices"), &[Mat4::IDENTITY; MAX_BONES]);
+        } else {
+            shader.uniform_mat4fv_array(cstr!("finalBonesMatrices"), &self.bones);
         }
 
+        BindVertexArray(self.vao);
+        DrawElements(TRIANGLES, self.indices.len() as i32, UNSIGNED_INT, ptr::null());
+        BindVertexArray(0);
+    }
+}
+
+impl Renderer {
+    pub fn add_skeletal_mesh_from_vertices_and_indices(&mut self, vertices: Vec<SkeletalVertex>, indices: Vec<u32>) -> Option<SkeletalMeshHandle> {
         let mesh = SkeletalMesh::new(&vertices, &indices);
-        
-        self.skeletal_meshes.insert(handle, mesh);
 
-        Some(handle)
+        Some(self.skeletal_meshes.insert(mesh))
     }
 
     pub fn add_skeletal_mesh(&mut self, mesh: SkeletalMesh) -> Option<SkeletalMeshHandle> {
-        let handle = SkeletalMeshHandle {id: self.skeletal_meshes.len()};
-
-        if self.skeletal_meshes.contains_key(&handle) {
-            println!("Skeletal mesh with handle {:?} already exists", handle);
-            return None;
-        }
-
-        self.skeletal_meshes.insert(handle, mesh);
-        Some(handle)
+        Some(self.skeletal_meshes.insert(mesh))
     }
 
     pub fn get_skeletal_mesh_mut(&mut self, handle: SkeletalMeshHandle) -> Option<&mut SkeletalMesh> {
-        self.skeletal_meshes.get_mut(&handle)
+        self.skeletal_meshes.get_mut(handle)
     }
 
     pub fn get_skeletal_mesh(&self, handle: SkeletalMeshHandle) -> Option<&SkeletalMesh> {
-        self.skeletal_meshes.get(&handle)
+        self.skeletal_meshes.get(handle)
     }
 
     pub fn destroy_skeletal_mesh(&mut self, handle: SkeletalMeshHandle) {
-        if self.skeletal_meshes.remove(&handle).is_some() {
+        if self.skeletal_meshes.remove(handle).is_some() {
 
         } else {
             println!("Failed to remove mesh, or there was no mesh to remove");
@@ -283,21 +258,4 @@ impl Drop for SkeletalMesh {
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
-pub struct SkeletalMeshHandle {
-    pub id: usize,
-}
-
-impl Index<SkeletalMeshHandle> for HashMap<SkeletalMeshHandle, Mesh> {
-    type Output = Mesh;
-
-    fn index(&self, handle: SkeletalMeshHandle) -> &Self::Output {
-        self.get(&handle).expect("No entry found for key")
-    }
-}
-
-impl IndexMut<SkeletalMeshHandle> for HashMap<SkeletalMeshHandle, Mesh> {
-    fn index_mut(&mut self, handle: SkeletalMeshHandle) -> &mut Self::Output {
-        self.get_mut(&handle).expect("No entry found for key")
-    }
-}
+pub type SkeletalMeshHandle = Handle<SkeletalMesh>;
@@ -0,0 +1,266 @@
+use std::ffi::CString;
+use std::mem::size_of;
+use std::ptr;
+
+use gl::{*, types::{GLint, GLsizei, GLuint}};
+use glam::{vec2, Mat2, Vec2, Vec3, Vec4};
+
+use crate::{cstr, gen_attrib_pointers, BlendState, Renderer, TextureHandle, UvRect, SPRITE_SHADER};
+
+/// a textured rectangle drawn by a `SpriteBatch`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprite {
+    /// where the origin ends up
+    pub position: Vec2,
+    /// counter clockwise, in radians
+    pub rotation: f32,
+    /// size in world units
+    pub scale: Vec2,
+    /// the point the sprite is positioned and rotated around, (0, 0) being the bottom
+    /// left corner and (1, 1) the top right
+    pub origin: Vec2,
+    pub uv: UvRect,
+    pub color: Vec4,
+    /// higher layers are drawn on top
+    pub layer: i32,
+    /// a plain colored rectangle when None
+    pub texture: Option<TextureHandle>,
+}
+
+impl Sprite {
+    pub fn new(position: Vec2, scale: Vec2) -> Self {
+        Self {
+            position,
+            rotation: 0.0,
+            scale,
+            origin: Vec2::splat(0.5),
+            uv: UvRect::FULL,
+            color: Vec4::ONE,
+            layer: 0,
+            texture: None,
+        }
+    }
+
+    pub fn textured(position: Vec2, scale: Vec2, texture: TextureHandle, uv: UvRect) -> Self {
+        Self {
+            texture: Some(texture),
+            uv,
+            ..Self::new(position, scale)
+        }
+    }
+}
+
+impl Default for Sprite {
+    fn default() -> Self {
+        Self::new(Vec2::ZERO, Vec2::ONE)
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SpriteVertex {
+    position: Vec3,
+    tex_coords: Vec2,
+    color: Vec4,
+}
+
+/// collects sprites during the frame and draws them with one draw call per
+/// texture change. sprites are sorted by layer and then by texture, so sprites
+/// on the same layer sharing a texture (like an atlas) are drawn together.
+/// drawn unlit and without depth testing, meant for orthographic cameras
+pub struct SpriteBatch {
+    sprites: Vec<Sprite>,
+    vertices: Vec<SpriteVertex>,
+
+    pub vao: GLuint,
+    pub vbo: GLuint,
+    pub ebo: GLuint,
+    /// how many sprites fit in the buffers before they have to grow
+    pub capacity: usize,
+    /// bound for sprites without a texture
+    white_texture: GLuint,
+
+    /// draw calls made by the last flush
+    pub draw_calls: usize,
+}
+
+impl SpriteBatch {
+    pub fn new() -> Self {
+        let mut batch = Self {
+            sprites: vec![],
+            vertices: vec![],
+            vao: 0,
+            vbo: 0,
+            ebo: 0,
+            capacity: 0,
+            white_texture: 0,
+            draw_calls: 0,
+        };
+
+        unsafe {
+            GenVertexArrays(1, &mut batch.vao);
+            GenBuffers(1, &mut batch.vbo);
+            GenBuffers(1, &mut batch.ebo);
+
+            BindVertexArray(batch.vao);
+            BindBuffer(ARRAY_BUFFER, batch.vbo);
+            BindBuffer(ELEMENT_ARRAY_BUFFER, batch.ebo);
+            gen_attrib_pointers!(SpriteVertex, 0 => position: 3, 1 => tex_coords: 2, 2 => color: 4);
+            BindVertexArray(0);
+
+            GenTextures(1, &mut batch.white_texture);
+            BindTexture(TEXTURE_2D, batch.white_texture);
+            TexImage2D(TEXTURE_2D, 0, RGBA8 as GLint, 1, 1, 0, RGBA, UNSIGNED_BYTE, [255u8; 4].as_ptr() as *const std::ffi::c_void);
+            TexParameteri(TEXTURE_2D, TEXTURE_MIN_FILTER, NEAREST as GLint);
+            TexParameteri(TEXTURE_2D, TEXTURE_MAG_FILTER, NEAREST as GLint);
+            BindTexture(TEXTURE_2D, 0);
+
+            batch.reserve(256);
+        }
+
+        batch
+    }
+
+    /// queues a sprite for the next flush
+    pub fn draw(&mut self, sprite: Sprite) {
+        self.sprites.push(sprite);
+    }
+
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    /// draws every queued sprite into the bound framebuffer and empties the batch.
+    /// call it after `renderer.draw()` so sprites end up on top of the scene
+    pub unsafe fn flush(&mut self, renderer: &Renderer) {
+        self.draw_calls = 0;
+        if self.sprites.is_empty() {
+            return;
+        }
+
+        let white_texture = self.white_texture;
+        let texture_id = |sprite: &Sprite| {
+            sprite.texture.and_then(|handle| renderer.textures.get(handle).copied()).unwrap_or(white_texture)
+        };
+
+        // stable, so sprites on the same layer and texture keep the order they were drawn in
+        let mut sprites = std::mem::take(&mut self.sprites);
+        sprites.sort_by_key(|sprite| (sprite.layer, texture_id(sprite)));
+
+        if sprites.len() > self.capacity {
+            self.reserve(sprites.len().next_power_of_two());
+        }
+
+        self.vertices.clear();
+        for sprite in &sprites {
+            let rotation = Mat2::from_angle(sprite.rotation);
+            let corners = [vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(1.0, 1.0), vec2(0.0, 1.0)];
+
+            for corner in corners {
+                let local = (corner - sprite.origin) * sprite.scale;
+                self.vertices.push(SpriteVertex {
+                    position: (sprite.position + rotation * local).extend(0.0),
+                    tex_coords: sprite.uv.map(corner),
+                    color: sprite.color,
+                });
+            }
+        }
+
+        renderer.upload_camera_block(renderer.debug_view.id());
+
+        let depth_test = IsEnabled(DEPTH_TEST) == TRUE;
+        let cull_face = IsEnabled(CULL_FACE) == TRUE;
+        let blend = BlendState::current();
+        Disable(DEPTH_TEST);
+        Disable(CULL_FACE);
+        Enable(BLEND);
+        BlendFunc(SRC_ALPHA, ONE_MINUS_SRC_ALPHA);
+
+        BindVertexArray(self.vao);
+        BindBuffer(ARRAY_BUFFER, self.vbo);
+        BufferSubData(
+            ARRAY_BUFFER,
+            0,
+            (self.vertices.len() * size_of::<SpriteVertex>()) as isize,
+            self.vertices.as_ptr() as *const std::ffi::c_void,
+        );
+
+        SPRITE_SHADER.use_shader();
+        SPRITE_SHADER.uniform_sampler(cstr!("sprite"), 0);
+        ActiveTexture(TEXTURE0);
+
+        // one draw call per run of sprites sharing a texture
+        let mut start = 0;
+        while start < sprites.len() {
+            let texture = texture_id(&sprites[start]);
+            let mut end = start + 1;
+            while end < sprites.len() && texture_id(&sprites[end]) == texture {
+                end += 1;
+            }
+
+            BindTexture(TEXTURE_2D, texture);
+            DrawElements(
+                TRIANGLES,
+                ((end - start) * 6) as GLsizei,
+                UNSIGNED_INT,
+                (start * 6 * size_of::<u32>()) as *const std::ffi::c_void,
+            );
+            self.draw_calls += 1;
+
+            start = end;
+        }
+
+        BindTexture(TEXTURE_2D, 0);
+        BindVertexArray(0);
+        UseProgram(0);
+        if depth_test { Enable(DEPTH_TEST); }
+        if cull_face { Enable(CULL_FACE); }
+        blend.restore();
+
+        // keep the allocation around for the next frame
+        sprites.clear();
+        self.sprites = sprites;
+    }
+
+    /// grows the buffers to fit `capacity` sprites
+    unsafe fn reserve(&mut self, capacity: usize) {
+        let indices: Vec<u32> = (0..capacity as u32)
+            .flat_map(|i| [0, 1, 2, 2, 3, 0].map(|index| i * 4 + index))
+            .collect();
+
+        BindVertexArray(self.vao);
+        BindBuffer(ARRAY_BUFFER, self.vbo);
+        BufferData(ARRAY_BUFFER, (capacity * 4 * size_of::<SpriteVertex>()) as isize, ptr::null(), DYNAMIC_DRAW);
+        BindBuffer(ELEMENT_ARRAY_BUFFER, self.ebo);
+        BufferData(
+            ELEMENT_ARRAY_BUFFER,
+            (indices.len() * size_of::<u32>()) as isize,
+            indices.as_ptr() as *const std::ffi::c_void,
+            STATIC_DRAW,
+        );
+        BindVertexArray(0);
+
+        self.capacity = capacity;
+    }
+}
+
+impl Default for SpriteBatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for SpriteBatch {
+    fn drop(&mut self) {
+        unsafe {
+            DeleteVertexArrays(1, &self.vao);
+            DeleteBuffers(1, &self.vbo);
+            DeleteBuffers(1, &self.ebo);
+            DeleteTextures(1, &self.white_texture);
+        }
+    }
+}
@@ -1,85 +1,253 @@
-use std::{collections::HashMap, ops::{Index, IndexMut}};
+use gl::types::{GLenum, GLfloat, GLint, GLsizei, GLuint, GLvoid};
+use image::error::{ParameterError, ParameterErrorKind};
+use image::{ImageError, RgbaImage};
 
-use gl::types::{GLint, GLsizei, GLuint, GLvoid};
+use crate::{Handle, Renderer};
 
-use crate::Renderer;
+/* from EXT_texture_filter_anisotropic, core since 4.6 so the 3.3 bindings lack them */
+const TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
 
-#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
-pub struct TextureHandle {
-    pub id: usize,
+/// the renderer keeps the raw opengl texture ids
+pub type TextureHandle = Handle<GLuint>;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Texture<'a> {
+    Path(&'a str),
+    Loaded(GLuint),
+    None,
 }
 
-impl<'a> Index<TextureHandle> for HashMap<TextureHandle, Texture<'a>> {
-    type Output = Texture<'a>;
+/// what happens to uvs outside of 0..1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WrapMode {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+}
 
-    fn index(&self, handle: TextureHandle) -> &Self::Output {
-        self.get(&handle).expect("No entry found for key")
+impl WrapMode {
+    fn gl_enum(&self) -> GLenum {
+        match self {
+            WrapMode::Repeat => gl::REPEAT,
+            WrapMode::MirroredRepeat => gl::MIRRORED_REPEAT,
+            WrapMode::ClampToEdge => gl::CLAMP_TO_EDGE,
+        }
     }
 }
 
-impl<'a> IndexMut<TextureHandle> for HashMap<TextureHandle, Texture<'a>> {
-    fn index_mut(&mut self, handle: TextureHandle) -> &mut Self::Output {
-        self.get_mut(&handle).expect("No entry found for key")
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterMode {
+    /// blocky, for pixel art
+    Nearest,
+    Linear,
+}
+
+/// how a texture is stored and sampled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureDescriptor {
+    /* horizontal and vertical */
+    pub wrap_s: WrapMode,
+    pub wrap_t: WrapMode,
+    /// used when the texture is drawn smaller than it is, mipmaps are picked the same way
+    pub min_filter: FilterMode,
+    /// used when the texture is drawn bigger than it is
+    pub mag_filter: FilterMode,
+    /// sharper textures at grazing angles, 1 turns it off. clamped to what the gpu supports
+    pub anisotropy: f32,
+    /// colors get converted to linear when sampled. right for albedo, wrong for
+    /// normal maps and other data
+    pub srgb: bool,
+    pub mipmaps: bool,
+    /// images start at the top, opengl textures at the bottom
+    pub flip: bool,
+}
+
+impl Default for TextureDescriptor {
+    fn default() -> Self {
+        Self {
+            wrap_s: WrapMode::Repeat,
+            wrap_t: WrapMode::Repeat,
+            min_filter: FilterMode::Linear,
+            mag_filter: FilterMode::Linear,
+            anisotropy: 1.0,
+            srgb: false,
+            mipmaps: true,
+            flip: true,
+        }
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
-pub enum Texture<'a> {
-    Path(&'a str),
-    Loaded(GLuint),
-    None,
+impl TextureDescriptor {
+    /// nearest filtering without mipmaps, clamped to the edges
+    pub fn pixelated() -> Self {
+        Self {
+            wrap_s: WrapMode::ClampToEdge,
+            wrap_t: WrapMode::ClampToEdge,
+            min_filter: FilterMode::Nearest,
+            mag_filter: FilterMode::Nearest,
+            mipmaps: false,
+            ..Default::default()
+        }
+    }
+
+    fn gl_min_filter(&self) -> GLenum {
+        match (self.min_filter, self.mipmaps) {
+            (FilterMode::Nearest, false) => gl::NEAREST,
+            (FilterMode::Linear, false) => gl::LINEAR,
+            (FilterMode::Nearest, true) => gl::NEAREST_MIPMAP_NEAREST,
+            (FilterMode::Linear, true) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+
+    fn gl_mag_filter(&self) -> GLenum {
+        match self.mag_filter {
+            FilterMode::Nearest => gl::NEAREST,
+            FilterMode::Linear => gl::LINEAR,
+        }
+    }
+}
+
+/// loads an image file with the default descriptor
+pub unsafe fn load_texture(path: &str) -> Result<GLuint, ImageError> {
+    load_texture_with(path, &TextureDescriptor::default())
+}
+
+pub unsafe fn load_texture_with(path: &str, descriptor: &TextureDescriptor) -> Result<GLuint, ImageError> {
+    let img = image::open(path)?.to_rgba8();
+
+    Ok(create_texture(&img, descriptor))
+}
+
+/// uploads rgba8 pixels, rows going from the top to the bottom like in an image file
+pub unsafe fn create_texture_from_pixels(width: u32, height: u32, pixels: &[u8], descriptor: &TextureDescriptor) -> Result<GLuint, ImageError> {
+    let Some(img) = RgbaImage::from_raw(width, height, pixels.to_vec()) else {
+        return Err(ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::DimensionMismatch)));
+    };
+
+    Ok(create_texture(&img, descriptor))
 }
 
-pub unsafe fn load_texture(path: &str) -> GLuint { 
-    let img = image::open(path).expect("Failed to load image");
-    
-    let img = img.flipv();
-    let width = img.width();
-    let height = img.height();
-    let raw_pixels = img.to_rgba8().into_raw();
-    
+pub unsafe fn create_texture(img: &RgbaImage, descriptor: &TextureDescriptor) -> GLuint {
+    let flipped;
+    let img = if descriptor.flip {
+        flipped = image::imageops::flip_vertical(img);
+        &flipped
+    } else {
+        img
+    };
+
     let mut texture: GLuint = 0;
     gl::GenTextures(1, &mut texture);
     gl::BindTexture(gl::TEXTURE_2D, texture);
 
-    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as GLint);
-    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as GLint);
-    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
-    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, descriptor.wrap_s.gl_enum() as GLint);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, descriptor.wrap_t.gl_enum() as GLint);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, descriptor.gl_min_filter() as GLint);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, descriptor.gl_mag_filter() as GLint);
+
+    if descriptor.anisotropy > 1.0 {
+        // stays 0 if the extension is missing
+        let mut max: GLfloat = 0.0;
+        gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max);
+        if max >= 1.0 {
+            gl::TexParameterf(gl::TEXTURE_2D, TEXTURE_MAX_ANISOTROPY, descriptor.anisotropy.min(max));
+        }
+    }
+
+    let internal_format = if descriptor.srgb { gl::SRGB8_ALPHA8 } else { gl::RGBA8 };
 
+    // rgba rows are always 4 byte aligned, but other code may have changed this
+    gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
     gl::TexImage2D(
         gl::TEXTURE_2D,
         0,
-        gl::RGBA as GLint,
-        width as GLsizei,
-        height as GLsizei,
+        internal_format as GLint,
+        img.width() as GLsizei,
+        img.height() as GLsizei,
         0,
         gl::RGBA,
         gl::UNSIGNED_BYTE,
-        raw_pixels.as_ptr() as *const GLvoid,
+        img.as_ptr() as *const GLvoid,
     );
 
-    gl::GenerateMipmap(gl::TEXTURE_2D);
+    if descriptor.mipmaps {
+        gl::GenerateMipmap(gl::TEXTURE_2D);
+    }
+
+    gl::BindTexture(gl::TEXTURE_2D, 0);
 
     texture
 }
 
+/// replaces a rectangle of rgba8 pixels. x, y and the rows follow opengl, starting
+/// at the bottom, so with `flip` the top row of the image is at the highest y.
+/// mipmaps are regenerated if the texture uses them
+pub unsafe fn update_texture(texture: GLuint, x: u32, y: u32, width: u32, height: u32, pixels: &[u8]) -> Result<(), ImageError> {
+    if pixels.len() != (width * height * 4) as usize {
+        return Err(ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::DimensionMismatch)));
+    }
+
+    gl::BindTexture(gl::TEXTURE_2D, texture);
+    gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+    gl::TexSubImage2D(
+        gl::TEXTURE_2D,
+        0,
+        x as GLint,
+        y as GLint,
+        width as GLsizei,
+        height as GLsizei,
+        gl::RGBA,
+        gl::UNSIGNED_BYTE,
+        pixels.as_ptr() as *const GLvoid,
+    );
+
+    let mut min_filter = 0;
+    gl::GetTexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, &mut min_filter);
+    if min_filter as GLenum != gl::NEAREST && min_filter as GLenum != gl::LINEAR {
+        gl::GenerateMipmap(gl::TEXTURE_2D);
+    }
+
+    gl::BindTexture(gl::TEXTURE_2D, 0);
+    Ok(())
+}
+
 impl Renderer {
-    pub fn add_texture(&mut self, path: &str) -> Option<TextureHandle> {
-        let handle = TextureHandle {id: self.textures.len()};
+    pub fn add_texture(&mut self, path: &str) -> Result<TextureHandle, ImageError> {
+        self.add_texture_with(path, &TextureDescriptor::default())
+    }
 
-        if self.textures.contains_key(&handle) {
-            println!("Texture with handle {:?} already exists", handle);
-            return None;
-        }
+    pub fn add_texture_with(&mut self, path: &str, descriptor: &TextureDescriptor) -> Result<TextureHandle, ImageError> {
+        let id = unsafe { load_texture_with(path, descriptor)? };
+        Ok(self.textures.insert(id))
+    }
+
+    /// see `create_texture_from_pixels`
+    pub fn add_texture_from_pixels(&mut self, width: u32, height: u32, pixels: &[u8], descriptor: &TextureDescriptor) -> Result<TextureHandle, ImageError> {
+        let id = unsafe { create_texture_from_pixels(width, height, pixels, descriptor)? };
+        Ok(self.textures.insert(id))
+    }
 
-        self.textures.insert(handle, unsafe {load_texture(path)});
-        Some(handle)
+    /// see `update_texture`
+    pub fn update_texture(&mut self, handle: TextureHandle, x: u32, y: u32, width: u32, height: u32, pixels: &[u8]) -> Result<(), ImageError> {
+        let Some(&texture) = self.textures.get(handle) else {
+            return Err(ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::Generic("no such texture".to_string()))));
+        };
+
+        unsafe { update_texture(texture, x, y, width, height, pixels) }
     }
 
+    /// textures of a render target belong to it, destroy the render target instead
     pub fn destroy_texture(&mut self, handle: TextureHandle) {
-        if self.textures.remove(&handle).is_some() {
+        if self.render_targets.values().any(|target| target.texture_handles.contains(&handle)) {
+            println!("Failed to remove texture, it belongs to a render target");
+            return;
+        }
 
+        if let Some(texture) = self.textures.remove(handle) {
+            unsafe {
+                gl::DeleteTextures(1, &texture);
+            }
         } else {
             println!("Failed to remove texture");
         }
@@ -94,4 +262,4 @@ impl Drop for Texture<'_> {
             }
         }
     }
-}
\ No newline at end of file
+}
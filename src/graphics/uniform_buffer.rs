@@ -0,0 +1,166 @@
+use std::ffi::CString;
+use std::mem::size_of;
+
+use gl::*;
+use glam::{Mat4, Vec4};
+
+use crate::{flat_shadow_view, ENVIRONMENT_TEXTURE_UNIT, Renderer, MAX_LIGHTS, MAX_SHADOW_MAPS, SHADOW_MAP_TEXTURE_UNIT};
+
+/* binding points shared by every shader, see `bind_uniform_blocks` */
+pub const CAMERA_BLOCK_BINDING: u32 = 0;
+pub const LIGHTS_BLOCK_BINDING: u32 = 1;
+
+/// a uniform buffer object bound to a fixed binding point
+#[derive(Debug)]
+pub struct UniformBuffer {
+    pub id: u32,
+    pub binding: u32,
+    pub size: usize,
+}
+
+impl UniformBuffer {
+    pub fn new(binding: u32, size: usize) -> Self {
+        let mut id = 0;
+
+        unsafe {
+            GenBuffers(1, &mut id);
+            BindBuffer(UNIFORM_BUFFER, id);
+            BufferData(UNIFORM_BUFFER, size as isize, std::ptr::null(), DYNAMIC_DRAW);
+            BindBuffer(UNIFORM_BUFFER, 0);
+            BindBufferBase(UNIFORM_BUFFER, binding, id);
+        }
+
+        Self { id, binding, size }
+    }
+
+    /// replaces the contents of the buffer, `T` has to follow the std140 layout
+    pub unsafe fn upload<T>(&self, data: &T) {
+        debug_assert!(size_of::<T>() <= self.size);
+
+        BindBuffer(UNIFORM_BUFFER, self.id);
+        BufferSubData(UNIFORM_BUFFER, 0, size_of::<T>() as isize, data as *const T as *const std::ffi::c_void);
+        BindBuffer(UNIFORM_BUFFER, 0);
+        BindBufferBase(UNIFORM_BUFFER, self.binding, self.id);
+    }
+}
+
+impl Drop for UniformBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            DeleteBuffers(1, &self.id);
+        }
+    }
+}
+
+/// the `Camera` block in the built in shaders
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CameraBlock {
+    pub view: Mat4,
+    pub proj: Mat4,
+    pub view_pos: Vec4,
+    /// x is the debug view, the rest is padding
+    pub debug_view: [i32; 4],
+}
+
+/// the `Lights` block in the built in shaders. every member is 16 byte aligned
+/// so the rust layout matches std140
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct LightsBlock {
+    pub ambient: Vec4,
+    pub light_count: [i32; 4],
+    /// x is the index of the light the map belongs to or -1, y is 1 for a cubemap
+    pub shadow_slot: [[i32; 4]; MAX_SHADOW_MAPS],
+    pub shadow_origin: [Vec4; MAX_SHADOW_MAPS],
+    pub shadow_params: [Vec4; MAX_SHADOW_MAPS],
+    pub shadow_matrix: [Mat4; MAX_SHADOW_MAPS],
+    pub light_pos: [Vec4; MAX_LIGHTS],
+    pub light_color: [Vec4; MAX_LIGHTS],
+    pub light_direction: [Vec4; MAX_LIGHTS],
+    pub light_attenuation: [Vec4; MAX_LIGHTS],
+}
+
+/// hooks a freshly linked program up to the shared uniform blocks and points
+/// its shadow map and environment samplers at their reserved texture units.
+/// programs that don't use them are left alone
+pub unsafe fn bind_uniform_blocks(program: u32) {
+    for (name, binding) in [("Camera", CAMERA_BLOCK_BINDING), ("Lights", LIGHTS_BLOCK_BINDING)] {
+        let name = CString::new(name).unwrap();
+        let index = GetUniformBlockIndex(program, name.as_ptr());
+        if index != INVALID_INDEX {
+            UniformBlockBinding(program, index, binding);
+        }
+    }
+
+    // every sampler has to point at its own unit even when unused, or it would
+    // alias with the 2D textures bound on unit 0
+    UseProgram(program);
+    for slot in 0..MAX_SHADOW_MAPS {
+        let cube = CString::new(format!("shadowMaps[{}]", slot)).unwrap();
+        let flat = CString::new(format!("flatShadowMaps[{}]", slot)).unwrap();
+        Uniform1i(GetUniformLocation(program, cube.as_ptr()), (SHADOW_MAP_TEXTURE_UNIT as usize + slot) as i32);
+        Uniform1i(GetUniformLocation(program, flat.as_ptr()), (SHADOW_MAP_TEXTURE_UNIT as usize + MAX_SHADOW_MAPS + slot) as i32);
+    }
+    let environment = CString::new("environment").unwrap();
+    Uniform1i(GetUniformLocation(program, environment.as_ptr()), ENVIRONMENT_TEXTURE_UNIT as i32);
+    UseProgram(0);
+}
+
+impl Renderer {
+    pub(crate) unsafe fn upload_camera_block(&self, debug_view: i32) {
+        self.camera_ubo.upload(&CameraBlock {
+            view: self.camera.view,
+            proj: self.camera.proj,
+            view_pos: self.camera.pos.extend(1.0),
+            debug_view: [debug_view, 0, 0, 0],
+        });
+    }
+
+    /// fills the camera and light blocks, once per frame
+    pub(crate) unsafe fn upload_uniform_buffers(&self) {
+        self.upload_camera_block(self.debug_view.id());
+
+        let mut block = LightsBlock {
+            ambient: self.ambient.extend(1.0),
+            light_count: [0; 4],
+            shadow_slot: [[-1, 0, 0, 0]; MAX_SHADOW_MAPS],
+            shadow_origin: [Vec4::ZERO; MAX_SHADOW_MAPS],
+            shadow_params: [Vec4::ZERO; MAX_SHADOW_MAPS],
+            shadow_matrix: [Mat4::IDENTITY; MAX_SHADOW_MAPS],
+            light_pos: [Vec4::ZERO; MAX_LIGHTS],
+            light_color: [Vec4::ZERO; MAX_LIGHTS],
+            light_direction: [Vec4::ZERO; MAX_LIGHTS],
+            light_attenuation: [Vec4::ZERO; MAX_LIGHTS],
+        };
+
+        for (i, light) in self.lights.values().take(MAX_LIGHTS).enumerate() {
+            let (inner, outer) = light.cone();
+            let att = light.attenuation;
+
+            block.light_pos[i] = light.position.extend(light.range);
+            block.light_color[i] = (light.color * light.intensity).extend(light.kind.id());
+            block.light_direction[i] = light.direction().extend(outer);
+            block.light_attenuation[i] = Vec4::new(att.constant, att.linear, att.quadratic, inner);
+
+            block.light_count[0] = i as i32 + 1;
+        }
+
+        for (slot, (i, light, map, settings)) in self.shadow_map_slots().enumerate() {
+            let (origin, matrix) = if map.cube {
+                (light.position, Mat4::IDENTITY)
+            } else {
+                flat_shadow_view(light, &settings, &self.camera)
+            };
+
+            block.shadow_slot[slot] = [i as i32, map.cube as i32, 0, 0];
+            block.shadow_origin[slot] = origin.extend(1.0);
+            block.shadow_matrix[slot] = matrix;
+            block.shadow_params[slot] = Vec4::new(
+                settings.bias, settings.pcf as f32, settings.far, 1.0 / map.resolution as f32
+            );
+        }
+
+        self.lights_ubo.upload(&block);
+    }
+}
@@ -6,6 +6,8 @@ mod ui;
 pub use util::*;
 pub use events::*;
 pub use graphics::*;
+// imgui has a Font of its own
+pub use graphics::Font;
 
 pub use ui::*;
 
@@ -20,7 +20,12 @@ fn main() {
     let mut floor = Quad::new(vec3(250.0, 250.0, 250.0), Vec4::ONE).mesh();
     floor.rotation = Quat::from_euler(EulerRot::XYZ, -3.1415 * 0.5, 0.0, 0.0);
     floor.position = vec3(-125.0, 0.0, 125.0);
-    floor.color = vec3(0.3, 0.3, 0.6);
+    floor.material = renderer.add_material({
+        let mut material = Material::default();
+        material.set_color(vec3(0.3, 0.3, 0.6));
+
+        material
+    });
     // renderer.add_mesh(floor).unwrap();
 
     for _ in 0..260 {
@@ -60,15 +65,24 @@ fn main() {
     for _ in 0..20 { 
         let mut pos = (rand_vec3() * 2.0 - 1.0) * 20.0;
         pos.y = sample_y(pos).unwrap();
-        renderer.add_light(Light { position: pos, color: rand_vec3() });
+        renderer.add_light(Light { position: pos, color: rand_vec3(), ..Default::default() });
     }
 
     renderer.add_mesh(great_mountains).unwrap();
 
+    // the sky moves this light around with the time of day
+    let sun = renderer.add_light(Light::directional(Vec3::NEG_Y, Vec3::ONE)).unwrap();
+    renderer.atmosphere = Some(AtmosphereSettings { sun: Some(sun), ..Default::default() });
+
 
     let mut sk_mesh = Model::load_skeletal(&scene);
-    sk_mesh.color = Vec3::ONE;
-    sk_mesh.set_texture(texture_handle, &renderer);
+    sk_mesh.material = renderer.add_material({
+        let mut material = Material::new(*RUSSIMP_SHADER);
+        material.set_color(Vec3::ONE);
+        material.set_diffuse_texture(texture_handle, &renderer);
+
+        material
+    });
     sk_mesh.scale = Vec3::ONE;
 
     let dance_anim = Animation::new(&dance);
@@ -118,10 +132,10 @@ fn main() {
         } else if el.event_handler.scroll.y > 0.5 {
             zoom *= 0.8;
         }
-        let goal = renderer.skeletal_meshes[&sk].position + vec3(1.0, 1.0, 1.0) * renderer.camera.front * -zoom + vec3(0.0, 1.2, 0.0);
+        let goal = renderer.skeletal_meshes[sk].position + vec3(1.0, 1.0, 1.0) * renderer.camera.front * -zoom + vec3(0.0, 1.2, 0.0);
         renderer.camera.update(goal, &el);
         
-        let mesh = &mut renderer.skeletal_meshes.get_mut(&sk).unwrap();
+        let mesh = &mut renderer.skeletal_meshes.get_mut(sk).unwrap();
         let mut front = renderer.camera.front;
         front.y = 0.0;
         let front = front.normalize();
@@ -168,19 +182,19 @@ fn main() {
         mesh.position.y = lerp(mesh.position.y, new_sample_y, speed * 0.05);
 
         animator.update(el.dt * (front * speed).length());
+        renderer.skeletal_meshes[sk].bones = animator.final_bone_matrices.clone();
         
         let frame = el.ui.frame(&mut el.window);
         frame.text("hello, world!");
         frame.text(format!("f: {:?}", 1.0 / el.dt));
+        if let Some(atmosphere) = renderer.atmosphere.as_mut() {
+            frame.slider("time of day", 0.0, 24.0, &mut atmosphere.time_of_day);
+        }
         
         unsafe {
             Clear(COLOR_BUFFER_BIT | DEPTH_BUFFER_BIT);
             ClearColor(0.1, 0.2, 0.3, 1.0);
             
-            RUSSIMP_SHADER.use_shader();
-            animator.upload_uniforms(&RUSSIMP_SHADER);
-            UseProgram(0);
-            
             renderer.draw();
             el.ui.draw();
         }
@@ -1,6 +1,6 @@
 use glam::{vec3, vec4, Mat4, Vec3, Vec3A, Vec4};
 
-use crate::{Camera, EventLoop, MeshHandle, Renderer};
+use crate::{Camera, EventLoop, InstancedMeshHandle, MeshHandle, Renderer, Vertex};
 
 use super::distance;
 
@@ -10,12 +10,14 @@ A set of utilities to help with culling meshes that are not in the view frustrum
 
 pub struct Culler {
     pub mesh_handles: Vec<MeshHandle>,
+    pub instanced_mesh_handles: Vec<InstancedMeshHandle>,
 }
 
 impl Culler {
     pub fn new(renderer: &Renderer) -> Self {
         Self {
             mesh_handles: vec![],
+            instanced_mesh_handles: vec![],
         }
     }
 
@@ -23,6 +25,12 @@ impl Culler {
         self.mesh_handles.push(handle);
     }
 
+    /// culls each instance of the mesh individually, culled instances are 
+    /// left out of the instance buffer on the next upload
+    pub fn add_instanced_mesh(&mut self, handle: InstancedMeshHandle) {
+        self.instanced_mesh_handles.push(handle);
+    }
+
     pub fn update(&mut self, renderer: &mut Renderer, el: &EventLoop) {
         let wh = el.size();
         let frustum = Frustum::sample_from_camera(
@@ -34,7 +42,7 @@ impl Culler {
         );
 
         for handle in &self.mesh_handles {
-            let mesh = renderer.meshes.get_mut(handle).unwrap();
+            let mesh = renderer.meshes.get_mut(*handle).unwrap();
 
             let biggest_radius = |mesh: &crate::Mesh| {
                 let mut size = 0.0;
@@ -51,26 +59,59 @@ impl Culler {
             };
 
             let volume = Sphere {
-                center: mesh.position,
+                center: mesh.world_position(),
                 radius: biggest_radius(&mesh),
                 // radius: 10.0
             };
 
-            let model_matrix = 
-                Mat4::from_translation(mesh.position) *
-                Mat4::from_quat(mesh.rotation) *
-                Mat4::from_scale(mesh.scale);
+            let model_matrix = mesh.model_matrix();
 
             if volume.is_on_frustrum(&frustum, model_matrix) {
-                mesh.hidden = false;
+                mesh.culled = false;
             } else {
-                mesh.hidden = true;
+                mesh.culled = true;
             }
             
         }
+
+        for handle in &self.instanced_mesh_handles {
+            let mesh = renderer.instanced_meshes.get_mut(*handle).unwrap();
+            let radius = biggest_radius(&mesh.vertices);
+            let mut changed = false;
+
+            for instance in mesh.instances.values_mut() {
+                let volume = Sphere {
+                    center: instance.position,
+                    radius: radius * instance.scale.max_element(),
+                };
+
+                let culled = !volume.is_on_frustrum(&frustum, instance.model_matrix());
+                if instance.culled != culled {
+                    instance.culled = culled;
+                    changed = true;
+                }
+            }
+
+            if changed {
+                mesh.needs_upload = true;
+            }
+        }
     }
 }
 
+fn biggest_radius(vertices: &[Vertex]) -> f32 {
+    let mut biggest = 0.0;
+
+    for v in vertices {
+        let size = v.position.length();
+        if size > biggest {
+            biggest = size;
+        }
+    }
+
+    biggest
+}
+
 struct Plane {
     normal: Vec3,
     distance: f32,
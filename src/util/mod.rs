@@ -1,6 +1,8 @@
 mod shapes;
 mod math;
+mod arena;
 pub mod culler;
 
 pub use math::*;
+pub use arena::*;
 pub use shapes::*;
\ No newline at end of file
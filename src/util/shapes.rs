@@ -1,6 +1,6 @@
 use glam::{vec2, vec3, Vec3, Vec4};
 
-use crate::graphics::{Mesh, Vertex};
+use crate::graphics::{Mesh, UvRect, Vertex};
 
 pub struct Quad{
     pub size: Vec3,
@@ -16,14 +16,19 @@ impl Quad{
     }
 
     pub fn mesh(&self) -> Mesh {
+        self.mesh_with_uv(UvRect::FULL)
+    }
+
+    /// shows only part of the texture, like a sprite from a `TextureAtlas`
+    pub fn mesh_with_uv(&self, uv: UvRect) -> Mesh {
         let half_width = self.size.x / 2.0;
         let half_height = self.size.y / 2.0;
         
         let vertices = vec![
-            Vertex::new(vec3(-half_width, -half_height, 0.0), self.color, vec2(0.0, 0.0), vec3(0., 0., 1.)),   // Bottom-left
-            Vertex::new(vec3(-half_width, half_height, 0.0), self.color, vec2(0.0, 1.0), vec3(0., 0., 1.)),    // Top-left
-            Vertex::new(vec3(half_width, -half_height, 0.0), self.color, vec2(1.0, 0.0), vec3(0., 0., 1.)),    // Bottom-right
-            Vertex::new(vec3(half_width, half_height, 0.0), self.color, vec2(1.0, 1.0), vec3(0., 0., 1.)),     // Top-right
+            Vertex::new(vec3(-half_width, -half_height, 0.0), self.color, uv.map(vec2(0.0, 0.0)), vec3(0., 0., 1.)),   // Bottom-left
+            Vertex::new(vec3(-half_width, half_height, 0.0), self.color, uv.map(vec2(0.0, 1.0)), vec3(0., 0., 1.)),    // Top-left
+            Vertex::new(vec3(half_width, -half_height, 0.0), self.color, uv.map(vec2(1.0, 0.0)), vec3(0., 0., 1.)),    // Bottom-right
+            Vertex::new(vec3(half_width, half_height, 0.0), self.color, uv.map(vec2(1.0, 1.0)), vec3(0., 0., 1.)),     // Top-right
         ];
 
         let indices = vec![0, 2, 1, 2, 3, 1];
@@ -0,0 +1,187 @@
+/*
+Renders small scenes without a window and compares them against the reference images
+in tests/golden. needs an opengl 3.3 context, so it's ignored by default. on a linux
+machine with no gpu:
+LIBGL_ALWAYS_SOFTWARE=1 xvfb-run cargo test --test golden_image -- --ignored
+
+CHAOS_BLESS=1 writes the reference images instead of comparing against them
+*/
+
+use std::path::{Path, PathBuf};
+
+use chaos_framework::*;
+
+const SIZE: u32 = 128;
+/* per channel, so different drivers rasterizing slightly differently still pass */
+const TOLERANCE: u8 = 8;
+const MAX_DIFFERENT_PIXELS: usize = (SIZE * SIZE / 100) as usize;
+
+/// every scene is drawn with a fresh renderer, looking along the camera's front from `eye`
+fn capture(el: &EventLoop, mut renderer: Renderer, eye: Vec3) -> image::RgbaImage {
+    renderer.update();
+    renderer.camera.update(eye, el);
+
+    unsafe {
+        ClearColor(0.1, 0.2, 0.3, 1.0);
+        renderer.capture(SIZE, SIZE).expect("Failed to capture the frame")
+    }
+}
+
+fn lit_cuboid(el: &EventLoop) -> image::RgbaImage {
+    let mut renderer = Renderer::new();
+
+    renderer.add_light(Light::point(vec3(2.0, 3.0, 4.0), Vec3::ONE)).unwrap();
+
+    let mut cuboid = Cuboid::new(Vec3::ONE, vec4(0.2, 0.6, 1.0, 1.0)).mesh();
+    cuboid.position = vec3(0.0, 0.0, -2.0);
+    cuboid.rotation = Quat::from_euler(EulerRot::XYZ, 0.5, 0.7, 0.0);
+    renderer.add_mesh(cuboid).unwrap();
+
+    capture(el, renderer, vec3(0.0, 0.0, 3.0))
+}
+
+fn textured_quad(el: &EventLoop) -> image::RgbaImage {
+    let mut renderer = Renderer::new();
+    renderer.ambient = Vec3::ONE;
+
+    // 4x4 checkerboard, red and white
+    let pixels: Vec<u8> = (0..16)
+        .flat_map(|i| if (i % 4 + i / 4) % 2 == 0 { [255, 255, 255, 255] } else { [255, 0, 0, 255] })
+        .collect();
+    let descriptor = TextureDescriptor {
+        min_filter: FilterMode::Nearest,
+        mag_filter: FilterMode::Nearest,
+        mipmaps: false,
+        ..Default::default()
+    };
+    let texture = renderer.add_texture_from_pixels(4, 4, &pixels, &descriptor).unwrap();
+
+    let mut material = Material::default();
+    material.set_diffuse_texture(texture, &renderer);
+    let material = renderer.add_material(material).unwrap();
+
+    let mut quad = Quad::new(vec3(2.0, 2.0, 1.0), Vec4::ONE).mesh();
+    quad.position = vec3(0.0, 0.0, -1.0);
+    quad.rotation = Quat::from_rotation_z(0.3);
+    quad.set_material(material);
+    renderer.add_mesh(quad).unwrap();
+
+    capture(el, renderer, vec3(0.0, 0.0, 2.0))
+}
+
+fn shadowed_cuboid(el: &EventLoop) -> image::RgbaImage {
+    let mut renderer = Renderer::new();
+
+    let mut sun = Light::directional(vec3(-0.4, -1.0, -0.3), Vec3::ONE);
+    sun.shadow = Some(ShadowSettings { extent: 6.0, far: 30.0, ..Default::default() });
+    renderer.add_light(sun).unwrap();
+
+    let mut floor = Quad::new(vec3(8.0, 8.0, 1.0), vec4(0.8, 0.8, 0.8, 1.0)).mesh();
+    floor.rotation = Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2);
+    renderer.add_mesh(floor).unwrap();
+
+    let mut cuboid = Cuboid::new(Vec3::ONE, vec4(1.0, 0.5, 0.2, 1.0)).mesh();
+    cuboid.position = vec3(0.0, 1.0, 0.0);
+    renderer.add_mesh(cuboid).unwrap();
+
+    renderer.camera.front = vec3(0.0, -0.6, -0.8).normalize();
+    capture(el, renderer, vec3(0.0, 4.0, 6.0))
+}
+
+/// a strip of two bones, the upper one bent to the side
+fn skinned_strip(el: &EventLoop) -> image::RgbaImage {
+    let mut renderer = Renderer::new();
+
+    renderer.add_light(Light::point(vec3(0.0, 1.0, 3.0), Vec3::ONE)).unwrap();
+
+    let vertex = |x: f32, y: f32, bone: i32| SkeletalVertex {
+        position: vec3(x, y, 0.0),
+        normal: Vec3::Z,
+        bone_ids: [bone, -1, -1, -1],
+        weights: [1.0, 0.0, 0.0, 0.0],
+        ..Default::default()
+    };
+    let vertices = vec![
+        vertex(-0.2, -1.0, 0), vertex(0.2, -1.0, 0),
+        vertex(-0.2, 0.0, 0), vertex(0.2, 0.0, 0),
+        vertex(-0.2, 1.0, 1), vertex(0.2, 1.0, 1),
+    ];
+    let indices = vec![0, 1, 3, 3, 2, 0, 2, 3, 5, 5, 4, 2];
+
+    let mut strip = SkeletalMesh::new(&vertices, &indices);
+    strip.position = vec3(0.0, 0.0, -1.0);
+    strip.bones = vec![Mat4::IDENTITY, Mat4::from_rotation_z(-0.8)];
+    renderer.add_skeletal_mesh(strip).unwrap();
+
+    capture(el, renderer, vec3(0.0, 0.0, 2.0))
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.png", name))
+}
+
+/// None if the image matches its reference, or a description of why it doesn't
+fn compare(name: &str, image: &image::RgbaImage) -> Option<String> {
+    let golden = golden_path(name);
+
+    if std::env::var_os("CHAOS_BLESS").is_some() {
+        std::fs::create_dir_all(golden.parent().unwrap()).unwrap();
+        image.save(&golden).expect("Failed to save the golden image");
+        println!("wrote {}", golden.display());
+        return None;
+    }
+
+    let Ok(expected) = image::open(&golden) else {
+        return Some(format!("{} is missing, run with CHAOS_BLESS=1 to create it", golden.display()));
+    };
+    let expected = expected.to_rgba8();
+    if expected.dimensions() != image.dimensions() {
+        return Some(format!("{} is {:?}, the render is {:?}", golden.display(), expected.dimensions(), image.dimensions()));
+    }
+
+    let different = image.pixels().zip(expected.pixels())
+        .filter(|(a, b)| a.0.iter().zip(b.0.iter()).any(|(a, b)| a.abs_diff(*b) > TOLERANCE))
+        .count();
+
+    if different > MAX_DIFFERENT_PIXELS {
+        let actual = golden.with_extension("actual.png");
+        image.save(&actual).unwrap();
+        return Some(format!("{} pixels differ from {}, the render was saved to {}", different, golden.display(), actual.display()));
+    }
+
+    None
+}
+
+/* one test for every scene, since the built in shaders only live as long as the first context */
+#[test]
+#[ignore = "needs an opengl context, run with --ignored"]
+fn scenes_match_golden_images() {
+    let el = EventLoop::headless(SIZE, SIZE);
+
+    unsafe {
+        Enable(DEPTH_TEST);
+    }
+
+    let scenes: [(&str, fn(&EventLoop) -> image::RgbaImage); 4] = [
+        ("cuboid", lit_cuboid),
+        ("textured_quad", textured_quad),
+        ("shadows", shadowed_cuboid),
+        ("skinning", skinned_strip),
+    ];
+
+    let mut failures = vec![];
+    for (name, scene) in scenes {
+        let image = scene(&el);
+        assert_eq!(image.dimensions(), (SIZE, SIZE));
+
+        // every scene has something in the middle of the frame, the corners are only clear color
+        if image.get_pixel(SIZE / 2, SIZE / 2) == image.get_pixel(0, 0) {
+            failures.push(format!("{} rendered nothing in the middle of the frame", name));
+            continue;
+        }
+
+        failures.extend(compare(name, &image));
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}